@@ -95,7 +95,7 @@ function LifeCanvasDrawer()
             canvas_height = canvas.height;
 
             image_data = context.createImageData(canvas_width, canvas_height);
-            image_data_data = new Int32Array(image_data.data.buffer);
+            image_data_data = new Uint32Array(image_data.data.buffer);
 
             for(var i = 0; i < width * height; i++)
             {
@@ -104,14 +104,6 @@ function LifeCanvasDrawer()
         }
     }
 
-    function draw_node(life, size, left, top)
-    {
-        var data = life.draw(left, top, size, canvas_height, canvas_width, canvas_offset_x, canvas_offset_y);
-        for(let i = 0; i < data.length; i += 2) {
-            fill_square(data[i], data[i + 1], Math.max(drawer.cell_width, 1));
-        }
-    }
-
     function fill_square(x, y, size)
     {
         var width = size - border_width,
@@ -173,16 +165,14 @@ function LifeCanvasDrawer()
         border_width = drawer.border_width * drawer.cell_width | 0;
         cell_color_rgb = color2rgb(drawer.cell_color);
 
-        var count = canvas_width * canvas_height;
-
-        for(var i = 0; i < count; i++)
-        {
-            image_data_data[i] = bg_color_int;
-        }
-
+        var cell_color_int = cell_color_rgb.r | cell_color_rgb.g << 8 | cell_color_rgb.b << 16 | 0xFF << 24;
         var size = Math.pow(2, life.get_level() - 1) * drawer.cell_width;
+        var view = new wasm_bindgen.View(-size, -size, 2 * size, canvas_offset_x, canvas_offset_y);
 
-        draw_node(life, 2 * size, -size, -size);
+        // the background and the cells are drawn into image_data by the engine
+        life.render_rgba(image_data_data, canvas_width, canvas_height, view,
+                         cell_color_int >>> 0, bg_color_int >>> 0, border_width);
+        view.free();
 
         context.putImageData(image_data, 0, 0);
     }
@@ -0,0 +1,35 @@
+#!/usr/bin/env node
+"use strict";
+
+// Classifies every rule of a range by how random soups behave in it, e.g.
+//
+//     node explore.js "B3x/S2x" 8 1024 > rules.csv
+//
+// The range is a rule where x stands for any other neighbour counts, or the
+// smallest and largest rule like B3/S23..B36/S236. The optional numbers are
+// the soups per rule and the generations to run them for.
+
+const fs = require("fs");
+const vm = require("vm");
+
+vm.runInThisContext(fs.readFileSync("./life.js"), { filename: "life.js" });
+wasm_bindgen.initSync({ module: fs.readFileSync("./life_bg.wasm") });
+
+const [range, seeds = "8", generations = "1024"] = process.argv.slice(2);
+
+if(!range)
+{
+    console.error("Usage: explore.js <rule range> [soups] [generations]");
+    process.exit(1);
+}
+
+try
+{
+    const csv = wasm_bindgen.LifeUniverse.classify_rules(range, Number(seeds), Number(generations));
+    process.stdout.write(csv);
+}
+catch(error)
+{
+    console.error("Invalid rule range: %s", error);
+    process.exit(1);
+}
@@ -28,7 +28,6 @@ var formats = (function()
         //parse_plaintext: parse_plaintext,
         parse_pattern: parse_pattern,
         rule2str: rule2str,
-        parse_rule: parse_rule,
         parse_comments: parse_comments,
         generate_rle: generate_rle,
     };
@@ -65,11 +64,9 @@ var formats = (function()
                     break;
 
                 case "rule":
-                    result.rule_s = parse_rule_rle(header_match[2], true);
-                    result.rule_b = parse_rule_rle(header_match[2], false);
-
-                    result.comment += "\nRule: " + rule2str(result.rule_s, result.rule_b) + "\n";
-                    result.rule = rule2str(result.rule_s, result.rule_b);
+                    // checked when the rule is set
+                    result.comment += "\nRule: " + header_match[2] + "\n";
+                    result.rule = header_match[2];
                     break;
 
                 case "alpha":
@@ -270,6 +267,7 @@ var formats = (function()
     {
         var result = {
                 comment: "",
+                comments: [],
                 urls: [],
                 short_comment: "",
             },
@@ -312,6 +310,7 @@ var formats = (function()
 
                     case "O":
                         result.author = line;
+                        cont = false;
                         break;
 
                     case "R":
@@ -347,6 +346,7 @@ var formats = (function()
                 else
                 {
                     result.comment += line;
+                    result.comments.push(line);
 
                     if(nl !== 70 && nl !== 80)
                     {
@@ -416,56 +416,6 @@ var formats = (function()
         return rule;
     }
 
-    function rule2str_rle(rule_s, rule_b)
-    {
-        let rule = formats.rule2str(rule_s, rule_b);
-        rule = rule.split("/");
-        rule = `B${rule[1]}/S${rule[0]}`;
-        return rule;
-    }
-
-    function parse_rule_rle(rule_str, survived)
-    {
-        rule_str = rule_str.split("/");
-
-        if(!rule_str[1])
-        {
-            return false;
-        }
-
-        if(Number(rule_str[0]))
-        {
-            return parse_rule(rule_str.join("/"), survived);
-        }
-
-        if(rule_str[0][0].toLowerCase() === "b")
-        {
-            rule_str.reverse();
-        }
-
-        return parse_rule(rule_str[0].substr(1) + "/" + rule_str[1].substr(1), survived);
-    }
-
-    function parse_rule(rule_str, survived)
-    {
-        var rule = 0,
-            parsed = rule_str.split("/")[survived ? 0 : 1];
-
-        for(var i = 0; i < parsed.length; i++)
-        {
-            var n = Number(parsed[i]);
-
-            if(isNaN(n) || rule & 1 << n)
-            {
-                return false;
-            }
-
-            rule |= 1 << n;
-        }
-
-        return rule;
-    }
-
     function* rle_generator(life, bounds)
     {
         function make(length, is_empty)
@@ -522,16 +472,35 @@ var formats = (function()
     }
 
     // implemented according to http://www.conwaylife.com/w/index.php?title=Run_Length_Encoded
+    // name and comments are added to the pattern info of the universe
     function generate_rle(life, name, comments)
     {
         const lines = [];
         const MAX_LINE_LENGTH = 70;
+        const info = life.get_pattern_info();
+
+        name = name || info.name;
 
         if(name)
         {
             lines.push("#N " + name);
         }
 
+        if(info.author)
+        {
+            lines.push("#O " + info.author);
+        }
+
+        const urls = info.urls;
+
+        if(info.source_url && !urls.includes(info.source_url))
+        {
+            urls.push(info.source_url);
+        }
+
+        comments = info.comments.concat(urls, comments.filter(c => !info.comments.includes(c)));
+        info.free();
+
         lines.push.apply(lines, comments.map(c => "#C " + c));
 
         const root = life.root;
@@ -540,8 +509,16 @@ var formats = (function()
         {
             const width = bounds.right - bounds.left + 1;
             const height = bounds.bottom - bounds.top + 1;
-            const rule = rule2str_rle(life.rule_s, life.rule_b);
-            lines.push(`x = ${width}, y = ${height}, rule = ${rule}`);
+            if(life.has_rule_table())
+            {
+                // the name of a rule table can't be read back as a rule
+                console.warn("The rule table %s is left out of the RLE", life.get_rule());
+                lines.push(`x = ${width}, y = ${height}`);
+            }
+            else
+            {
+                lines.push(`x = ${width}, y = ${height}, rule = ${life.get_rule()}`);
+            }
         }
 
         let current_line = "";
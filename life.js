@@ -1,290 +1,1238 @@
-let wasm_bindgen;
-(function() {
-    const __exports = {};
+let wasm_bindgen = (function(exports) {
     let script_src;
     if (typeof document !== 'undefined' && document.currentScript !== null) {
         script_src = new URL(document.currentScript.src, location.href).toString();
     }
-    let wasm = undefined;
 
-    const cachedTextDecoder = (typeof TextDecoder !== 'undefined' ? new TextDecoder('utf-8', { ignoreBOM: true, fatal: true }) : { decode: () => { throw Error('TextDecoder not available') } } );
+    /**
+     * @enum {0 | 1 | 2 | 3}
+     */
+    const BooleanOp = Object.freeze({
+        Union: 0, "0": "Union",
+        Intersect: 1, "1": "Intersect",
+        Difference: 2, "2": "Difference",
+        Xor: 3, "3": "Xor",
+    });
+    exports.BooleanOp = BooleanOp;
 
-    if (typeof TextDecoder !== 'undefined') { cachedTextDecoder.decode(); };
+    /**
+     * @enum {0 | 1 | 2}
+     */
+    const Engine = Object.freeze({
+        Hashlife: 0, "0": "Hashlife",
+        QuickLife: 1, "1": "QuickLife",
+        Auto: 2, "2": "Auto",
+    });
+    exports.Engine = Engine;
 
-    let cachedUint8ArrayMemory0 = null;
-
-    function getUint8ArrayMemory0() {
-        if (cachedUint8ArrayMemory0 === null || cachedUint8ArrayMemory0.byteLength === 0) {
-            cachedUint8ArrayMemory0 = new Uint8Array(wasm.memory.buffer);
+    class LifeUniverse {
+        static __wrap(ptr) {
+            const obj = Object.create(LifeUniverse.prototype);
+            obj.__wbg_ptr = ptr;
+            LifeUniverseFinalization.register(obj, obj.__wbg_ptr, obj);
+            return obj;
         }
-        return cachedUint8ArrayMemory0;
-    }
-
-    function getStringFromWasm0(ptr, len) {
-        ptr = ptr >>> 0;
-        return cachedTextDecoder.decode(getUint8ArrayMemory0().subarray(ptr, ptr + len));
-    }
-
-    let cachedFloat64ArrayMemory0 = null;
-
-    function getFloat64ArrayMemory0() {
-        if (cachedFloat64ArrayMemory0 === null || cachedFloat64ArrayMemory0.byteLength === 0) {
-            cachedFloat64ArrayMemory0 = new Float64Array(wasm.memory.buffer);
+        __destroy_into_raw() {
+            const ptr = this.__wbg_ptr;
+            this.__wbg_ptr = 0;
+            LifeUniverseFinalization.unregister(this);
+            return ptr;
+        }
+        free() {
+            const ptr = this.__destroy_into_raw();
+            wasm.__wbg_lifeuniverse_free(ptr, 0);
+        }
+        /**
+         * @param {number} left
+         * @param {number} top
+         * @param {number} width
+         * @param {number} height
+         * @param {number} scale
+         * @param {number} cell_color
+         * @param {number} background
+         */
+        begin_png(left, top, width, height, scale, cell_color, background) {
+            wasm.lifeuniverse_begin_png(this.__wbg_ptr, left, top, width, height, scale, cell_color, background);
+        }
+        /**
+         * @param {boolean} is_single
+         */
+        begin_step(is_single) {
+            wasm.lifeuniverse_begin_step(this.__wbg_ptr, is_single);
+        }
+        /**
+         * @param {LifeUniverse} on_tile
+         * @param {LifeUniverse} off_tile
+         * @param {LifeUniverse} pattern
+         * @param {number} level
+         */
+        build_metapattern(on_tile, off_tile, pattern, level) {
+            _assertClass(on_tile, LifeUniverse);
+            _assertClass(off_tile, LifeUniverse);
+            _assertClass(pattern, LifeUniverse);
+            wasm.lifeuniverse_build_metapattern(this.__wbg_ptr, on_tile.__wbg_ptr, off_tile.__wbg_ptr, pattern.__wbg_ptr, level);
+        }
+        /**
+         * @returns {boolean}
+         */
+        can_redo() {
+            const ret = wasm.lifeuniverse_can_redo(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        can_undo() {
+            const ret = wasm.lifeuniverse_can_undo(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        cancel_png() {
+            wasm.lifeuniverse_cancel_png(this.__wbg_ptr);
+        }
+        cancel_step() {
+            wasm.lifeuniverse_cancel_step(this.__wbg_ptr);
+        }
+        /**
+         * @param {string} range
+         * @param {number} seeds
+         * @param {number} generations
+         * @returns {string}
+         */
+        static classify_rules(range, seeds, generations) {
+            let deferred3_0;
+            let deferred3_1;
+            try {
+                const ptr0 = passStringToWasm0(range, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+                const len0 = WASM_VECTOR_LEN;
+                const ret = wasm.lifeuniverse_classify_rules(ptr0, len0, seeds, generations);
+                var ptr2 = ret[0];
+                var len2 = ret[1];
+                if (ret[3]) {
+                    ptr2 = 0; len2 = 0;
+                    throw takeFromExternrefTable0(ret[2]);
+                }
+                deferred3_0 = ptr2;
+                deferred3_1 = len2;
+                return getStringFromWasm0(ptr2, len2);
+            } finally {
+                wasm.__wbindgen_free(deferred3_0, deferred3_1, 1);
+            }
+        }
+        clear_custom_rule() {
+            wasm.lifeuniverse_clear_custom_rule(this.__wbg_ptr);
+        }
+        clear_history() {
+            wasm.lifeuniverse_clear_history(this.__wbg_ptr);
+        }
+        clear_pattern() {
+            wasm.lifeuniverse_clear_pattern(this.__wbg_ptr);
+        }
+        /**
+         * @param {LifeUniverse} other
+         * @param {BooleanOp} op
+         */
+        combine(other, op) {
+            _assertClass(other, LifeUniverse);
+            wasm.lifeuniverse_combine(this.__wbg_ptr, other.__wbg_ptr, op);
+        }
+        /**
+         * @param {LifeUniverse} other
+         * @returns {LifeUniverse}
+         */
+        compare_with(other) {
+            _assertClass(other, LifeUniverse);
+            const ret = wasm.lifeuniverse_compare_with(this.__wbg_ptr, other.__wbg_ptr);
+            return LifeUniverse.__wrap(ret);
+        }
+        /**
+         * @param {number} generations
+         * @returns {LifeUniverse}
+         */
+        compute_envelope(generations) {
+            const ret = wasm.lifeuniverse_compute_envelope(this.__wbg_ptr, generations);
+            return LifeUniverse.__wrap(ret);
+        }
+        /**
+         * @returns {Uint8Array | undefined}
+         */
+        continue_png() {
+            const ret = wasm.lifeuniverse_continue_png(this.__wbg_ptr);
+            let v1;
+            if (ret[0] !== 0) {
+                v1 = getArrayU8FromWasm0(ret[0], ret[1]).slice();
+                wasm.__wbindgen_free(ret[0], ret[1] * 1, 1);
+            }
+            return v1;
+        }
+        /**
+         * @param {number} budget_ms
+         * @returns {StepStatus}
+         */
+        continue_step(budget_ms) {
+            const ret = wasm.lifeuniverse_continue_step(this.__wbg_ptr, budget_ms);
+            return ret;
+        }
+        /**
+         * @param {string} name
+         * @returns {boolean}
+         */
+        delete_snapshot(name) {
+            const ptr0 = passStringToWasm0(name, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            const ret = wasm.lifeuniverse_delete_snapshot(this.__wbg_ptr, ptr0, len0);
+            return ret !== 0;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {number} size
+         * @param {number} height
+         * @param {number} width
+         * @param {number} offset_x
+         * @param {number} offset_y
+         * @returns {Float64Array}
+         */
+        draw(x, y, size, height, width, offset_x, offset_y) {
+            const ret = wasm.lifeuniverse_draw(this.__wbg_ptr, x, y, size, height, width, offset_x, offset_y);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {number} size
+         * @param {number} height
+         * @param {number} width
+         * @param {number} offset_x
+         * @param {number} offset_y
+         * @returns {Float64Array}
+         */
+        draw_cell_history(x, y, size, height, width, offset_x, offset_y) {
+            const ret = wasm.lifeuniverse_draw_cell_history(this.__wbg_ptr, x, y, size, height, width, offset_x, offset_y);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {number} size
+         * @param {number} height
+         * @param {number} width
+         * @param {number} offset_x
+         * @param {number} offset_y
+         * @returns {Float64Array}
+         */
+        draw_density(x, y, size, height, width, offset_x, offset_y) {
+            const ret = wasm.lifeuniverse_draw_density(this.__wbg_ptr, x, y, size, height, width, offset_x, offset_y);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {number} size
+         * @param {number} height
+         * @param {number} width
+         * @param {number} offset_x
+         * @param {number} offset_y
+         * @returns {Float64Array}
+         */
+        draw_states(x, y, size, height, width, offset_x, offset_y) {
+            const ret = wasm.lifeuniverse_draw_states(this.__wbg_ptr, x, y, size, height, width, offset_x, offset_y);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @param {number} left
+         * @param {number} top
+         * @param {number} width
+         * @param {number} height
+         * @param {number} scale
+         * @param {number} frames
+         * @param {number} step
+         * @param {number} fps
+         * @param {number} cell_color
+         * @param {number} background
+         * @returns {Uint8Array}
+         */
+        export_animation(left, top, width, height, scale, frames, step, fps, cell_color, background) {
+            const ret = wasm.lifeuniverse_export_animation(this.__wbg_ptr, left, top, width, height, scale, frames, step, fps, cell_color, background);
+            var v1 = getArrayU8FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 1, 1);
+            return v1;
+        }
+        /**
+         * @param {number} left
+         * @param {number} top
+         * @param {number} width
+         * @param {number} height
+         * @param {number} scale
+         * @param {number} cell_color
+         * @param {number} background
+         * @returns {Uint8Array}
+         */
+        export_png(left, top, width, height, scale, cell_color, background) {
+            const ret = wasm.lifeuniverse_export_png(this.__wbg_ptr, left, top, width, height, scale, cell_color, background);
+            var v1 = getArrayU8FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 1, 1);
+            return v1;
+        }
+        /**
+         * @returns {Engine}
+         */
+        get_active_engine() {
+            const ret = wasm.lifeuniverse_get_active_engine(this.__wbg_ptr);
+            return ret;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @returns {boolean}
+         */
+        get_bit(x, y) {
+            const ret = wasm.lifeuniverse_get_bit(this.__wbg_ptr, x, y);
+            return ret !== 0;
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @returns {number}
+         */
+        get_cell_state(x, y) {
+            const ret = wasm.lifeuniverse_get_cell_state(this.__wbg_ptr, x, y);
+            return ret;
+        }
+        /**
+         * @returns {string}
+         */
+        get_custom_rule_name() {
+            let deferred1_0;
+            let deferred1_1;
+            try {
+                const ret = wasm.lifeuniverse_get_custom_rule_name(this.__wbg_ptr);
+                deferred1_0 = ret[0];
+                deferred1_1 = ret[1];
+                return getStringFromWasm0(ret[0], ret[1]);
+            } finally {
+                wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+            }
+        }
+        /**
+         * @returns {Engine}
+         */
+        get_engine() {
+            const ret = wasm.lifeuniverse_get_engine(this.__wbg_ptr);
+            return ret;
+        }
+        /**
+         * @returns {number}
+         */
+        get_generation() {
+            const ret = wasm.lifeuniverse_get_generation(this.__wbg_ptr);
+            return ret;
+        }
+        /**
+         * @returns {number}
+         */
+        get_level() {
+            const ret = wasm.lifeuniverse_get_level(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {number}
+         */
+        get_memory_limit() {
+            const ret = wasm.lifeuniverse_get_memory_limit(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {Float64Array}
+         */
+        get_memory_stats() {
+            const ret = wasm.lifeuniverse_get_memory_stats(this.__wbg_ptr);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @returns {PatternInfo}
+         */
+        get_pattern_info() {
+            const ret = wasm.lifeuniverse_get_pattern_info(this.__wbg_ptr);
+            return PatternInfo.__wrap(ret);
+        }
+        /**
+         * @returns {number}
+         */
+        get_population() {
+            const ret = wasm.lifeuniverse_get_population(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {Float64Array}
+         */
+        get_root_bounds() {
+            const ret = wasm.lifeuniverse_get_root_bounds(this.__wbg_ptr);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @returns {number}
+         */
+        get_rule_b() {
+            const ret = wasm.lifeuniverse_get_rule_b(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {string}
+         */
+        get_rule() {
+            let deferred1_0;
+            let deferred1_1;
+            try {
+                const ret = wasm.lifeuniverse_get_rule(this.__wbg_ptr);
+                deferred1_0 = ret[0];
+                deferred1_1 = ret[1];
+                return getStringFromWasm0(ret[0], ret[1]);
+            } finally {
+                wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+            }
+        }
+        /**
+         * @returns {number}
+         */
+        get_rule_s() {
+            const ret = wasm.lifeuniverse_get_rule_s(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {number}
+         */
+        get_states() {
+            const ret = wasm.lifeuniverse_get_states(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {Float64Array}
+         */
+        get_stats() {
+            const ret = wasm.lifeuniverse_get_stats(this.__wbg_ptr);
+            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
+            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
+            return v1;
+        }
+        /**
+         * @returns {number}
+         */
+        get_step() {
+            const ret = wasm.lifeuniverse_get_step(this.__wbg_ptr);
+            return ret >>> 0;
+        }
+        /**
+         * @returns {number}
+         */
+        get_step_progress() {
+            const ret = wasm.lifeuniverse_get_step_progress(this.__wbg_ptr);
+            return ret;
+        }
+        /**
+         * @returns {NodeStore}
+         */
+        get_store() {
+            const ret = wasm.lifeuniverse_get_store(this.__wbg_ptr);
+            return NodeStore.__wrap(ret);
+        }
+        /**
+         * @returns {boolean}
+         */
+        has_cell_history() {
+            const ret = wasm.lifeuniverse_has_cell_history(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        has_custom_rule() {
+            const ret = wasm.lifeuniverse_has_custom_rule(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        has_pending_step() {
+            const ret = wasm.lifeuniverse_has_pending_step(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        has_rewind_state() {
+            const ret = wasm.lifeuniverse_has_rewind_state(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        has_rule_table() {
+            const ret = wasm.lifeuniverse_has_rule_table(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @returns {string[]}
+         */
+        list_snapshots() {
+            const ret = wasm.lifeuniverse_list_snapshots(this.__wbg_ptr);
+            var v1 = getArrayJsValueFromWasm0(ret[0], ret[1]);
+            wasm.__wbindgen_free(ret[0], ret[1] * 4, 4);
+            return v1;
+        }
+        constructor() {
+            const ret = wasm.lifeuniverse_new();
+            this.__wbg_ptr = ret;
+            LifeUniverseFinalization.register(this, this.__wbg_ptr, this);
+            return this;
+        }
+        /**
+         * @param {boolean} is_single
+         * @returns {boolean}
+         */
+        next_generation(is_single) {
+            const ret = wasm.lifeuniverse_next_generation(this.__wbg_ptr, is_single);
+            return ret !== 0;
+        }
+        /**
+         * @returns {boolean}
+         */
+        redo() {
+            const ret = wasm.lifeuniverse_redo(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @param {Float32Array} buffer
+         * @param {number} width
+         * @param {number} height
+         * @param {View} view
+         */
+        render_density(buffer, width, height, view) {
+            var ptr0 = passArrayF32ToWasm0(buffer, wasm.__wbindgen_malloc);
+            var len0 = WASM_VECTOR_LEN;
+            _assertClass(view, View);
+            const ret = wasm.lifeuniverse_render_density(this.__wbg_ptr, ptr0, len0, buffer, width, height, view.__wbg_ptr);
+            if (ret[1]) {
+                throw takeFromExternrefTable0(ret[0]);
+            }
+        }
+        /**
+         * @param {Uint32Array} buffer
+         * @param {number} width
+         * @param {number} height
+         * @param {View} view
+         * @param {number} cell_color
+         * @param {number} background
+         * @param {number} border_width
+         */
+        render_rgba(buffer, width, height, view, cell_color, background, border_width) {
+            var ptr0 = passArray32ToWasm0(buffer, wasm.__wbindgen_malloc);
+            var len0 = WASM_VECTOR_LEN;
+            _assertClass(view, View);
+            const ret = wasm.lifeuniverse_render_rgba(this.__wbg_ptr, ptr0, len0, buffer, width, height, view.__wbg_ptr, cell_color, background, border_width);
+            if (ret[1]) {
+                throw takeFromExternrefTable0(ret[0]);
+            }
+        }
+        reset_stats() {
+            wasm.lifeuniverse_reset_stats(this.__wbg_ptr);
+        }
+        /**
+         * @param {string} name
+         * @returns {boolean}
+         */
+        restore(name) {
+            const ptr0 = passStringToWasm0(name, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            const ret = wasm.lifeuniverse_restore(this.__wbg_ptr, ptr0, len0);
+            return ret !== 0;
+        }
+        restore_rewind_state() {
+            wasm.lifeuniverse_restore_rewind_state(this.__wbg_ptr);
+        }
+        save_rewind_state() {
+            wasm.lifeuniverse_save_rewind_state(this.__wbg_ptr);
+        }
+        save_undo_state() {
+            wasm.lifeuniverse_save_undo_state(this.__wbg_ptr);
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {boolean} living
+         */
+        set_bit(x, y, living) {
+            wasm.lifeuniverse_set_bit(this.__wbg_ptr, x, y, living);
+        }
+        /**
+         * @param {number} x
+         * @param {number} y
+         * @param {number} state
+         */
+        set_cell_state(x, y, state) {
+            wasm.lifeuniverse_set_cell_state(this.__wbg_ptr, x, y, state);
+        }
+        /**
+         * @param {Engine} engine
+         */
+        set_engine(engine) {
+            wasm.lifeuniverse_set_engine(this.__wbg_ptr, engine);
+        }
+        /**
+         * @param {number} limit
+         */
+        set_history_limit(limit) {
+            wasm.lifeuniverse_set_history_limit(this.__wbg_ptr, limit);
+        }
+        /**
+         * @param {number} bytes
+         */
+        set_memory_limit(bytes) {
+            wasm.lifeuniverse_set_memory_limit(this.__wbg_ptr, bytes);
+        }
+        /**
+         * @param {PatternInfo} info
+         */
+        set_pattern_info(info) {
+            _assertClass(info, PatternInfo);
+            wasm.lifeuniverse_set_pattern_info(this.__wbg_ptr, info.__wbg_ptr);
+        }
+        /**
+         * @param {string} rule
+         */
+        set_rule(rule) {
+            const ptr0 = passStringToWasm0(rule, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            const ret = wasm.lifeuniverse_set_rule(this.__wbg_ptr, ptr0, len0);
+            if (ret[1]) {
+                throw takeFromExternrefTable0(ret[0]);
+            }
+        }
+        /**
+         * @param {string} source
+         */
+        set_rule_table(source) {
+            const ptr0 = passStringToWasm0(source, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            const ret = wasm.lifeuniverse_set_rule_table(this.__wbg_ptr, ptr0, len0);
+            if (ret[1]) {
+                throw takeFromExternrefTable0(ret[0]);
+            }
+        }
+        /**
+         * @param {number} s
+         * @param {number} b
+         */
+        set_rules(s, b) {
+            wasm.lifeuniverse_set_rules(this.__wbg_ptr, s, b);
+        }
+        /**
+         * @param {number} step
+         * @returns {number}
+         */
+        set_step(step) {
+            const ret = wasm.lifeuniverse_set_step(this.__wbg_ptr, step);
+            return ret >>> 0;
+        }
+        /**
+         * @param {Int32Array} field_x
+         * @param {Int32Array} field_y
+         */
+        setup_field(field_x, field_y) {
+            const ptr0 = passArray32ToWasm0(field_x, wasm.__wbindgen_malloc);
+            const len0 = WASM_VECTOR_LEN;
+            const ptr1 = passArray32ToWasm0(field_y, wasm.__wbindgen_malloc);
+            const len1 = WASM_VECTOR_LEN;
+            wasm.lifeuniverse_setup_field(this.__wbg_ptr, ptr0, len0, ptr1, len1);
+        }
+        /**
+         * @param {string} name
+         */
+        snapshot(name) {
+            const ptr0 = passStringToWasm0(name, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.lifeuniverse_snapshot(this.__wbg_ptr, ptr0, len0);
+        }
+        start_cell_history() {
+            wasm.lifeuniverse_start_cell_history(this.__wbg_ptr);
+        }
+        stop_cell_history() {
+            wasm.lifeuniverse_stop_cell_history(this.__wbg_ptr);
+        }
+        /**
+         * @returns {boolean}
+         */
+        undo() {
+            const ret = wasm.lifeuniverse_undo(this.__wbg_ptr);
+            return ret !== 0;
+        }
+        /**
+         * @param {NodeStore} store
+         * @returns {LifeUniverse}
+         */
+        static with_store(store) {
+            _assertClass(store, NodeStore);
+            const ret = wasm.lifeuniverse_with_store(store.__wbg_ptr);
+            return LifeUniverse.__wrap(ret);
         }
-        return cachedFloat64ArrayMemory0;
-    }
-
-    function getArrayF64FromWasm0(ptr, len) {
-        ptr = ptr >>> 0;
-        return getFloat64ArrayMemory0().subarray(ptr / 8, ptr / 8 + len);
     }
+    if (Symbol.dispose) LifeUniverse.prototype[Symbol.dispose] = LifeUniverse.prototype.free;
+    exports.LifeUniverse = LifeUniverse;
 
-    let cachedUint32ArrayMemory0 = null;
-
-    function getUint32ArrayMemory0() {
-        if (cachedUint32ArrayMemory0 === null || cachedUint32ArrayMemory0.byteLength === 0) {
-            cachedUint32ArrayMemory0 = new Uint32Array(wasm.memory.buffer);
+    class NodeStore {
+        static __wrap(ptr) {
+            const obj = Object.create(NodeStore.prototype);
+            obj.__wbg_ptr = ptr;
+            NodeStoreFinalization.register(obj, obj.__wbg_ptr, obj);
+            return obj;
+        }
+        __destroy_into_raw() {
+            const ptr = this.__wbg_ptr;
+            this.__wbg_ptr = 0;
+            NodeStoreFinalization.unregister(this);
+            return ptr;
+        }
+        free() {
+            const ptr = this.__destroy_into_raw();
+            wasm.__wbg_nodestore_free(ptr, 0);
+        }
+        constructor() {
+            const ret = wasm.nodestore_new();
+            this.__wbg_ptr = ret;
+            NodeStoreFinalization.register(this, this.__wbg_ptr, this);
+            return this;
         }
-        return cachedUint32ArrayMemory0;
     }
+    if (Symbol.dispose) NodeStore.prototype[Symbol.dispose] = NodeStore.prototype.free;
+    exports.NodeStore = NodeStore;
 
-    let WASM_VECTOR_LEN = 0;
-
-    function passArray32ToWasm0(arg, malloc) {
-        const ptr = malloc(arg.length * 4, 4) >>> 0;
-        getUint32ArrayMemory0().set(arg, ptr / 4);
-        WASM_VECTOR_LEN = arg.length;
-        return ptr;
+    class PatternInfo {
+        static __wrap(ptr) {
+            const obj = Object.create(PatternInfo.prototype);
+            obj.__wbg_ptr = ptr;
+            PatternInfoFinalization.register(obj, obj.__wbg_ptr, obj);
+            return obj;
+        }
+        __destroy_into_raw() {
+            const ptr = this.__wbg_ptr;
+            this.__wbg_ptr = 0;
+            PatternInfoFinalization.unregister(this);
+            return ptr;
+        }
+        free() {
+            const ptr = this.__destroy_into_raw();
+            wasm.__wbg_patterninfo_free(ptr, 0);
+        }
+        /**
+         * @returns {string}
+         */
+        get author() {
+            let deferred1_0;
+            let deferred1_1;
+            try {
+                const ret = wasm.__wbg_get_patterninfo_author(this.__wbg_ptr);
+                deferred1_0 = ret[0];
+                deferred1_1 = ret[1];
+                return getStringFromWasm0(ret[0], ret[1]);
+            } finally {
+                wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+            }
+        }
+        /**
+         * @returns {string[]}
+         */
+        get comments() {
+            const ret = wasm.__wbg_get_patterninfo_comments(this.__wbg_ptr);
+            var v1 = getArrayJsValueFromWasm0(ret[0], ret[1]);
+            wasm.__wbindgen_free(ret[0], ret[1] * 4, 4);
+            return v1;
+        }
+        /**
+         * @returns {string}
+         */
+        get name() {
+            let deferred1_0;
+            let deferred1_1;
+            try {
+                const ret = wasm.__wbg_get_patterninfo_name(this.__wbg_ptr);
+                deferred1_0 = ret[0];
+                deferred1_1 = ret[1];
+                return getStringFromWasm0(ret[0], ret[1]);
+            } finally {
+                wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+            }
+        }
+        /**
+         * @returns {string}
+         */
+        get source_url() {
+            let deferred1_0;
+            let deferred1_1;
+            try {
+                const ret = wasm.__wbg_get_patterninfo_source_url(this.__wbg_ptr);
+                deferred1_0 = ret[0];
+                deferred1_1 = ret[1];
+                return getStringFromWasm0(ret[0], ret[1]);
+            } finally {
+                wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+            }
+        }
+        /**
+         * @returns {string[]}
+         */
+        get urls() {
+            const ret = wasm.__wbg_get_patterninfo_urls(this.__wbg_ptr);
+            var v1 = getArrayJsValueFromWasm0(ret[0], ret[1]);
+            wasm.__wbindgen_free(ret[0], ret[1] * 4, 4);
+            return v1;
+        }
+        constructor() {
+            const ret = wasm.patterninfo_new();
+            this.__wbg_ptr = ret;
+            PatternInfoFinalization.register(this, this.__wbg_ptr, this);
+            return this;
+        }
+        /**
+         * @param {string} arg0
+         */
+        set author(arg0) {
+            const ptr0 = passStringToWasm0(arg0, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.__wbg_set_patterninfo_author(this.__wbg_ptr, ptr0, len0);
+        }
+        /**
+         * @param {string[]} arg0
+         */
+        set comments(arg0) {
+            const ptr0 = passArrayJsValueToWasm0(arg0, wasm.__wbindgen_malloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.__wbg_set_patterninfo_comments(this.__wbg_ptr, ptr0, len0);
+        }
+        /**
+         * @param {string} arg0
+         */
+        set name(arg0) {
+            const ptr0 = passStringToWasm0(arg0, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.__wbg_set_patterninfo_name(this.__wbg_ptr, ptr0, len0);
+        }
+        /**
+         * @param {string} arg0
+         */
+        set source_url(arg0) {
+            const ptr0 = passStringToWasm0(arg0, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.__wbg_set_patterninfo_source_url(this.__wbg_ptr, ptr0, len0);
+        }
+        /**
+         * @param {string[]} arg0
+         */
+        set urls(arg0) {
+            const ptr0 = passArrayJsValueToWasm0(arg0, wasm.__wbindgen_malloc);
+            const len0 = WASM_VECTOR_LEN;
+            wasm.__wbg_set_patterninfo_urls(this.__wbg_ptr, ptr0, len0);
+        }
     }
+    if (Symbol.dispose) PatternInfo.prototype[Symbol.dispose] = PatternInfo.prototype.free;
+    exports.PatternInfo = PatternInfo;
 
-    const LifeUniverseFinalization = (typeof FinalizationRegistry === 'undefined')
-        ? { register: () => {}, unregister: () => {} }
-        : new FinalizationRegistry(ptr => wasm.__wbg_lifeuniverse_free(ptr >>> 0, 1));
-
-    class LifeUniverse {
+    /**
+     * @enum {0 | 1 | 2 | 3 | 4}
+     */
+    const StepStatus = Object.freeze({
+        Working: 0, "0": "Working",
+        Done: 1, "1": "Done",
+        OutOfMemory: 2, "2": "OutOfMemory",
+        Edited: 3, "3": "Edited",
+        Idle: 4, "4": "Idle",
+    });
+    exports.StepStatus = StepStatus;
 
+    class View {
         __destroy_into_raw() {
             const ptr = this.__wbg_ptr;
             this.__wbg_ptr = 0;
-            LifeUniverseFinalization.unregister(this);
+            ViewFinalization.unregister(this);
             return ptr;
         }
-
         free() {
             const ptr = this.__destroy_into_raw();
-            wasm.__wbg_lifeuniverse_free(ptr, 0);
-        }
-        clear_pattern() {
-            wasm.lifeuniverse_clear_pattern(this.__wbg_ptr);
-        }
-        constructor() {
-            const ret = wasm.lifeuniverse_new();
-            this.__wbg_ptr = ret >>> 0;
-            LifeUniverseFinalization.register(this, this.__wbg_ptr, this);
-            return this;
-        }
-        save_rewind_state() {
-            wasm.lifeuniverse_save_rewind_state(this.__wbg_ptr);
-        }
-        restore_rewind_state() {
-            wasm.lifeuniverse_restore_rewind_state(this.__wbg_ptr);
-        }
-        /**
-         * @returns {boolean}
-         */
-        has_rewind_state() {
-            const ret = wasm.lifeuniverse_has_rewind_state(this.__wbg_ptr);
-            return ret !== 0;
+            wasm.__wbg_view_free(ptr, 0);
         }
         /**
-         * @param {number} x
-         * @param {number} y
-         * @param {boolean} living
+         * @returns {number}
          */
-        set_bit(x, y, living) {
-            wasm.lifeuniverse_set_bit(this.__wbg_ptr, x, y, living);
+        get offset_x() {
+            const ret = wasm.__wbg_get_view_offset_x(this.__wbg_ptr);
+            return ret;
         }
         /**
-         * @param {number} x
-         * @param {number} y
-         * @returns {boolean}
+         * @returns {number}
          */
-        get_bit(x, y) {
-            const ret = wasm.lifeuniverse_get_bit(this.__wbg_ptr, x, y);
-            return ret !== 0;
+        get offset_y() {
+            const ret = wasm.__wbg_get_view_offset_y(this.__wbg_ptr);
+            return ret;
         }
         /**
-         * @returns {Float64Array}
+         * @returns {number}
          */
-        get_root_bounds() {
-            const ret = wasm.lifeuniverse_get_root_bounds(this.__wbg_ptr);
-            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
-            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
-            return v1;
+        get size() {
+            const ret = wasm.__wbg_get_view_size(this.__wbg_ptr);
+            return ret;
         }
         /**
-         * @param {boolean} is_single
+         * @returns {number}
          */
-        next_generation(is_single) {
-            wasm.lifeuniverse_next_generation(this.__wbg_ptr, is_single);
+        get x() {
+            const ret = wasm.__wbg_get_view_x(this.__wbg_ptr);
+            return ret;
         }
         /**
-         * @param {Int32Array} field_x
-         * @param {Int32Array} field_y
+         * @returns {number}
          */
-        setup_field(field_x, field_y) {
-            const ptr0 = passArray32ToWasm0(field_x, wasm.__wbindgen_malloc);
-            const len0 = WASM_VECTOR_LEN;
-            const ptr1 = passArray32ToWasm0(field_y, wasm.__wbindgen_malloc);
-            const len1 = WASM_VECTOR_LEN;
-            wasm.lifeuniverse_setup_field(this.__wbg_ptr, ptr0, len0, ptr1, len1);
+        get y() {
+            const ret = wasm.__wbg_get_view_y(this.__wbg_ptr);
+            return ret;
         }
         /**
-         * @returns {number}
+         * @param {number} arg0
          */
-        get_step() {
-            const ret = wasm.lifeuniverse_get_step(this.__wbg_ptr);
-            return ret >>> 0;
+        set offset_x(arg0) {
+            wasm.__wbg_set_view_offset_x(this.__wbg_ptr, arg0);
         }
         /**
-         * @param {number} step
+         * @param {number} arg0
          */
-        set_step(step) {
-            wasm.lifeuniverse_set_step(this.__wbg_ptr, step);
+        set offset_y(arg0) {
+            wasm.__wbg_set_view_offset_y(this.__wbg_ptr, arg0);
         }
         /**
-         * @param {number} s
-         * @param {number} b
+         * @param {number} arg0
          */
-        set_rules(s, b) {
-            wasm.lifeuniverse_set_rules(this.__wbg_ptr, s, b);
+        set size(arg0) {
+            wasm.__wbg_set_view_size(this.__wbg_ptr, arg0);
         }
         /**
-         * @returns {number}
+         * @param {number} arg0
          */
-        get_rule_s() {
-            const ret = wasm.lifeuniverse_get_rule_s(this.__wbg_ptr);
-            return ret >>> 0;
+        set x(arg0) {
+            wasm.__wbg_set_view_x(this.__wbg_ptr, arg0);
         }
         /**
-         * @returns {number}
+         * @param {number} arg0
          */
-        get_rule_b() {
-            const ret = wasm.lifeuniverse_get_rule_b(this.__wbg_ptr);
-            return ret >>> 0;
+        set y(arg0) {
+            wasm.__wbg_set_view_y(this.__wbg_ptr, arg0);
         }
         /**
          * @param {number} x
          * @param {number} y
          * @param {number} size
-         * @param {number} height
-         * @param {number} width
          * @param {number} offset_x
          * @param {number} offset_y
-         * @returns {Float64Array}
          */
-        draw(x, y, size, height, width, offset_x, offset_y) {
-            const ret = wasm.lifeuniverse_draw(this.__wbg_ptr, x, y, size, height, width, offset_x, offset_y);
-            var v1 = getArrayF64FromWasm0(ret[0], ret[1]).slice();
-            wasm.__wbindgen_free(ret[0], ret[1] * 8, 8);
-            return v1;
+        constructor(x, y, size, offset_x, offset_y) {
+            const ret = wasm.view_new(x, y, size, offset_x, offset_y);
+            this.__wbg_ptr = ret;
+            ViewFinalization.register(this, this.__wbg_ptr, this);
+            return this;
         }
-        /**
-         * @returns {number}
-         */
-        get_generation() {
-            const ret = wasm.lifeuniverse_get_generation(this.__wbg_ptr);
-            return ret;
+    }
+    if (Symbol.dispose) View.prototype[Symbol.dispose] = View.prototype.free;
+    exports.View = View;
+    function __wbg_get_imports() {
+        const import0 = {
+            __proto__: null,
+            __wbg___wbindgen_copy_to_typed_array_88899a52af046901: function(arg0, arg1, arg2) {
+                new Uint8Array(arg2.buffer, arg2.byteOffset, arg2.byteLength).set(getArrayU8FromWasm0(arg0, arg1));
+            },
+            __wbg___wbindgen_string_get_0380ccaa2f57f0d9: function(arg0, arg1) {
+                const obj = arg1;
+                const ret = typeof(obj) === 'string' ? obj : undefined;
+                var ptr1 = isLikeNone(ret) ? 0 : passStringToWasm0(ret, wasm.__wbindgen_malloc, wasm.__wbindgen_realloc);
+                var len1 = WASM_VECTOR_LEN;
+                getDataViewMemory0().setInt32(arg0 + 4 * 1, len1, true);
+                getDataViewMemory0().setInt32(arg0 + 4 * 0, ptr1, true);
+            },
+            __wbg___wbindgen_throw_41e9ee4f547fc59a: function(arg0, arg1) {
+                throw new Error(getStringFromWasm0(arg0, arg1));
+            },
+            __wbg_now_77b12f178cc4d41e: function() {
+                const ret = performance.now();
+                return ret;
+            },
+            __wbindgen_generic_0000000000000001: function(arg0, arg1) {
+                // Cast intrinsic for `Ref(String) -> Externref`.
+                const ret = getStringFromWasm0(arg0, arg1);
+                return ret;
+            },
+            __wbindgen_init_externref_table: function() {
+                const table = wasm.__wbindgen_externrefs;
+                const offset = table.grow(4);
+                table.set(0, undefined);
+                table.set(offset + 0, undefined);
+                table.set(offset + 1, null);
+                table.set(offset + 2, true);
+                table.set(offset + 3, false);
+            },
+        };
+        return {
+            __proto__: null,
+            "./life_bg.js": import0,
+        };
+    }
+
+    const LifeUniverseFinalization = (typeof FinalizationRegistry === 'undefined')
+        ? { register: () => {}, unregister: () => {} }
+        : new FinalizationRegistry(ptr => wasm.__wbg_lifeuniverse_free(ptr, 1));
+    const NodeStoreFinalization = (typeof FinalizationRegistry === 'undefined')
+        ? { register: () => {}, unregister: () => {} }
+        : new FinalizationRegistry(ptr => wasm.__wbg_nodestore_free(ptr, 1));
+    const PatternInfoFinalization = (typeof FinalizationRegistry === 'undefined')
+        ? { register: () => {}, unregister: () => {} }
+        : new FinalizationRegistry(ptr => wasm.__wbg_patterninfo_free(ptr, 1));
+    const ViewFinalization = (typeof FinalizationRegistry === 'undefined')
+        ? { register: () => {}, unregister: () => {} }
+        : new FinalizationRegistry(ptr => wasm.__wbg_view_free(ptr, 1));
+
+    function addToExternrefTable0(obj) {
+        const idx = wasm.__externref_table_alloc();
+        wasm.__wbindgen_externrefs.set(idx, obj);
+        return idx;
+    }
+
+    function _assertClass(instance, klass) {
+        if (!(instance instanceof klass)) {
+            throw new Error(`expected instance of ${klass.name}`);
         }
-        /**
-         * @returns {number}
-         */
-        get_population() {
-            const ret = wasm.lifeuniverse_get_population(this.__wbg_ptr);
-            return ret >>> 0;
+    }
+
+    function getArrayF64FromWasm0(ptr, len) {
+        ptr = ptr >>> 0;
+        return getFloat64ArrayMemory0().subarray(ptr / 8, ptr / 8 + len);
+    }
+
+    function getArrayJsValueFromWasm0(ptr, len) {
+        ptr = ptr >>> 0;
+        const mem = getDataViewMemory0();
+        const result = [];
+        for (let i = ptr; i < ptr + 4 * len; i += 4) {
+            result.push(wasm.__wbindgen_externrefs.get(mem.getUint32(i, true)));
         }
-        /**
-         * @returns {number}
-         */
-        get_level() {
-            const ret = wasm.lifeuniverse_get_level(this.__wbg_ptr);
-            return ret >>> 0;
+        wasm.__externref_drop_slice(ptr, len);
+        return result;
+    }
+
+    function getArrayU8FromWasm0(ptr, len) {
+        ptr = ptr >>> 0;
+        return getUint8ArrayMemory0().subarray(ptr / 1, ptr / 1 + len);
+    }
+
+    let cachedDataViewMemory0 = null;
+    function getDataViewMemory0() {
+        if (cachedDataViewMemory0 === null || cachedDataViewMemory0.buffer.detached === true || (cachedDataViewMemory0.buffer.detached === undefined && cachedDataViewMemory0.buffer !== wasm.memory.buffer)) {
+            cachedDataViewMemory0 = new DataView(wasm.memory.buffer);
         }
+        return cachedDataViewMemory0;
     }
-    __exports.LifeUniverse = LifeUniverse;
 
-    async function __wbg_load(module, imports) {
-        if (typeof Response === 'function' && module instanceof Response) {
-            if (typeof WebAssembly.instantiateStreaming === 'function') {
-                try {
-                    return await WebAssembly.instantiateStreaming(module, imports);
+    let cachedFloat32ArrayMemory0 = null;
+    function getFloat32ArrayMemory0() {
+        if (cachedFloat32ArrayMemory0 === null || cachedFloat32ArrayMemory0.byteLength === 0) {
+            cachedFloat32ArrayMemory0 = new Float32Array(wasm.memory.buffer);
+        }
+        return cachedFloat32ArrayMemory0;
+    }
 
-                } catch (e) {
-                    if (module.headers.get('Content-Type') != 'application/wasm') {
-                        console.warn("`WebAssembly.instantiateStreaming` failed because your server does not serve Wasm with `application/wasm` MIME type. Falling back to `WebAssembly.instantiate` which is slower. Original error:\n", e);
+    let cachedFloat64ArrayMemory0 = null;
+    function getFloat64ArrayMemory0() {
+        if (cachedFloat64ArrayMemory0 === null || cachedFloat64ArrayMemory0.byteLength === 0) {
+            cachedFloat64ArrayMemory0 = new Float64Array(wasm.memory.buffer);
+        }
+        return cachedFloat64ArrayMemory0;
+    }
 
-                    } else {
-                        throw e;
-                    }
-                }
-            }
+    function getStringFromWasm0(ptr, len) {
+        return decodeText(ptr >>> 0, len);
+    }
 
-            const bytes = await module.arrayBuffer();
-            return await WebAssembly.instantiate(bytes, imports);
+    let cachedUint32ArrayMemory0 = null;
+    function getUint32ArrayMemory0() {
+        if (cachedUint32ArrayMemory0 === null || cachedUint32ArrayMemory0.byteLength === 0) {
+            cachedUint32ArrayMemory0 = new Uint32Array(wasm.memory.buffer);
+        }
+        return cachedUint32ArrayMemory0;
+    }
 
-        } else {
-            const instance = await WebAssembly.instantiate(module, imports);
+    let cachedUint8ArrayMemory0 = null;
+    function getUint8ArrayMemory0() {
+        if (cachedUint8ArrayMemory0 === null || cachedUint8ArrayMemory0.byteLength === 0) {
+            cachedUint8ArrayMemory0 = new Uint8Array(wasm.memory.buffer);
+        }
+        return cachedUint8ArrayMemory0;
+    }
 
-            if (instance instanceof WebAssembly.Instance) {
-                return { instance, module };
+    function isLikeNone(x) {
+        return x === undefined || x === null;
+    }
 
-            } else {
-                return instance;
+    function passArray32ToWasm0(arg, malloc) {
+        const ptr = malloc(arg.length * 4, 4) >>> 0;
+        getUint32ArrayMemory0().set(arg, ptr / 4);
+        WASM_VECTOR_LEN = arg.length;
+        return ptr;
+    }
+
+    function passArrayF32ToWasm0(arg, malloc) {
+        const ptr = malloc(arg.length * 4, 4) >>> 0;
+        getFloat32ArrayMemory0().set(arg, ptr / 4);
+        WASM_VECTOR_LEN = arg.length;
+        return ptr;
+    }
+
+    function passArrayJsValueToWasm0(array, malloc) {
+        const ptr = malloc(array.length * 4, 4) >>> 0;
+        for (let i = 0; i < array.length; i++) {
+            const add = addToExternrefTable0(array[i]);
+            getDataViewMemory0().setUint32(ptr + 4 * i, add, true);
+        }
+        WASM_VECTOR_LEN = array.length;
+        return ptr;
+    }
+
+    function passStringToWasm0(arg, malloc, realloc) {
+        if (realloc === undefined) {
+            const buf = cachedTextEncoder.encode(arg);
+            const ptr = malloc(buf.length, 1) >>> 0;
+            getUint8ArrayMemory0().subarray(ptr, ptr + buf.length).set(buf);
+            WASM_VECTOR_LEN = buf.length;
+            return ptr;
+        }
+
+        let len = arg.length;
+        let ptr = malloc(len, 1) >>> 0;
+
+        const mem = getUint8ArrayMemory0();
+
+        let offset = 0;
+
+        for (; offset < len; offset++) {
+            const code = arg.charCodeAt(offset);
+            if (code > 0x7F) break;
+            mem[ptr + offset] = code;
+        }
+        if (offset !== len) {
+            if (offset !== 0) {
+                arg = arg.slice(offset);
             }
+            ptr = realloc(ptr, len, len = offset + arg.length * 3, 1) >>> 0;
+            const view = getUint8ArrayMemory0().subarray(ptr + offset, ptr + len);
+            const ret = cachedTextEncoder.encodeInto(arg, view);
+
+            offset += ret.written;
+            ptr = realloc(ptr, len, offset, 1) >>> 0;
         }
+
+        WASM_VECTOR_LEN = offset;
+        return ptr;
     }
 
-    function __wbg_get_imports() {
-        const imports = {};
-        imports.wbg = {};
-        imports.wbg.__wbindgen_init_externref_table = function() {
-            const table = wasm.__wbindgen_export_0;
-            const offset = table.grow(4);
-            table.set(0, undefined);
-            table.set(offset + 0, undefined);
-            table.set(offset + 1, null);
-            table.set(offset + 2, true);
-            table.set(offset + 3, false);
-            ;
-        };
-        imports.wbg.__wbindgen_throw = function(arg0, arg1) {
-            throw new Error(getStringFromWasm0(arg0, arg1));
-        };
+    function takeFromExternrefTable0(idx) {
+        const value = wasm.__wbindgen_externrefs.get(idx);
+        wasm.__externref_table_dealloc(idx);
+        return value;
+    }
 
-        return imports;
+    let cachedTextDecoder = new TextDecoder('utf-8', { ignoreBOM: true, fatal: true });
+    cachedTextDecoder.decode();
+    function decodeText(ptr, len) {
+        return cachedTextDecoder.decode(getUint8ArrayMemory0().subarray(ptr, ptr + len));
     }
 
-    function __wbg_init_memory(imports, memory) {
+    const cachedTextEncoder = new TextEncoder();
 
+    if (!('encodeInto' in cachedTextEncoder)) {
+        cachedTextEncoder.encodeInto = function (arg, view) {
+            const buf = cachedTextEncoder.encode(arg);
+            view.set(buf);
+            return {
+                read: arg.length,
+                written: buf.length
+            };
+        };
     }
 
+    let WASM_VECTOR_LEN = 0;
+
+    let wasmModule, wasmInstance, wasm;
     function __wbg_finalize_init(instance, module) {
+        wasmInstance = instance;
         wasm = instance.exports;
-        __wbg_init.__wbindgen_wasm_module = module;
+        wasmModule = module;
+        cachedDataViewMemory0 = null;
+        cachedFloat32ArrayMemory0 = null;
         cachedFloat64ArrayMemory0 = null;
         cachedUint32ArrayMemory0 = null;
         cachedUint8ArrayMemory0 = null;
-
-
         wasm.__wbindgen_start();
         return wasm;
     }
 
+    async function __wbg_load(module, imports) {
+        if (typeof Response === 'function' && module instanceof Response) {
+            if (!module.ok) {
+                throw new Error(`failed to fetch Wasm: ${module.status} ${module.statusText} fetching '${module.url}'`);
+            }
+
+            if (typeof WebAssembly.instantiateStreaming === 'function') {
+                try {
+                    return await WebAssembly.instantiateStreaming(module, imports);
+                } catch (e) {
+                    const validResponse = expectedResponseType(module.type);
+
+                    if (validResponse && module.headers.get('Content-Type') !== 'application/wasm') {
+                        console.warn("`WebAssembly.instantiateStreaming` failed because your server does not serve Wasm with `application/wasm` MIME type. Falling back to `WebAssembly.instantiate` which is slower. Original error:\n", e);
+
+                    } else { throw e; }
+                }
+            }
+
+            const bytes = await module.arrayBuffer();
+            return await WebAssembly.instantiate(bytes, imports);
+        } else {
+            const instance = await WebAssembly.instantiate(module, imports);
+
+            if (instance instanceof WebAssembly.Instance) {
+                return { instance, module };
+            } else {
+                return instance;
+            }
+        }
+
+        function expectedResponseType(type) {
+            switch (type) {
+                case 'basic': case 'cors': case 'default': return true;
+            }
+            return false;
+        }
+    }
+
     function initSync(module) {
         if (wasm !== undefined) return wasm;
 
 
-        if (typeof module !== 'undefined') {
+        if (module !== undefined) {
             if (Object.getPrototypeOf(module) === Object.prototype) {
                 ({module} = module)
             } else {
@@ -293,15 +1241,10 @@ let wasm_bindgen;
         }
 
         const imports = __wbg_get_imports();
-
-        __wbg_init_memory(imports);
-
         if (!(module instanceof WebAssembly.Module)) {
             module = new WebAssembly.Module(module);
         }
-
         const instance = new WebAssembly.Instance(module, imports);
-
         return __wbg_finalize_init(instance, module);
     }
 
@@ -309,7 +1252,7 @@ let wasm_bindgen;
         if (wasm !== undefined) return wasm;
 
 
-        if (typeof module_or_path !== 'undefined') {
+        if (module_or_path !== undefined) {
             if (Object.getPrototypeOf(module_or_path) === Object.prototype) {
                 ({module_or_path} = module_or_path)
             } else {
@@ -317,8 +1260,8 @@ let wasm_bindgen;
             }
         }
 
-        if (typeof module_or_path === 'undefined' && typeof script_src !== 'undefined') {
-            module_or_path = script_src.replace(/\.js$/, '_bg.wasm');
+        if (module_or_path === undefined && script_src !== undefined) {
+            module_or_path = script_src.replace(/\.js$/, "_bg.wasm");
         }
         const imports = __wbg_get_imports();
 
@@ -326,13 +1269,10 @@ let wasm_bindgen;
             module_or_path = fetch(module_or_path);
         }
 
-        __wbg_init_memory(imports);
-
         const { instance, module } = await __wbg_load(await module_or_path, imports);
 
         return __wbg_finalize_init(instance, module);
     }
 
-    wasm_bindgen = Object.assign(__wbg_init, { initSync }, __exports);
-
-})();
+    return Object.assign(__wbg_init, { initSync }, exports);
+})({ __proto__: null });
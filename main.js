@@ -21,7 +21,10 @@ var
     /** @const */
     DEFAULT_BORDER = 0.25,
     /** @const */
-    DEFAULT_FPS = 20;
+    DEFAULT_FPS = 20,
+    // milliseconds of each frame spent on the next generation while running
+    /** @const */
+    STEP_BUDGET = 15;
 
 
 (function()
@@ -234,56 +237,55 @@ var
 
         function try_load_meta()
         {
-            // loading metapixels is broken now, keep this for later
-            load_random();
+            var universes = {};
 
-            /*
-            var otca_on, otca_off, otca_pattern;
-
-            show_overlay("loading_popup");
-            http_get_multiple([
-                {
-                    url : pattern_path + "otcametapixel.rle",
+            // every file goes into a universe of its own, centred on the origin
+            function loader(name)
+            {
+                return {
+                    url : pattern_path + name + ".rle",
                     onready : function(result)
                     {
-                        var field = formats.parse_rle(result).field;
+                        var pattern = formats.parse_pattern(result);
 
-                        life.move_field(field, -5, -5);
-                        life.setup_field(field);
-
-                        otca_on = life.root.se.nw;
+                        if(!pattern.error)
+                        {
+                            universes[name] = new wasm_bindgen.LifeUniverse();
+                            universes[name].setup_field(pattern.field_x, pattern.field_y);
+                        }
                     }
-                },
-                {
-                    url : pattern_path + "otcametapixeloff.rle",
-                    onready : function(result)
-                    {
-                        var field = formats.parse_rle(result).field;
-
-                        life.move_field(field, -5, -5);
-                        life.setup_field(field);
+                };
+            }
 
-                        otca_off = life.root.se.nw;
-                    }
-                },
-                {
-                    url : pattern_path + pattern_parameter + ".rle",
-                    onready : function(result)
-                    {
-                        otca_pattern = formats.parse_rle(result).field;
-                    }
-                }
+            show_overlay("loading_popup");
+            http_get_multiple([
+                loader("otcametapixel"),
+                loader("otcametapixeloff"),
+                loader(pattern_parameter),
             ],
             function()
             {
-                load_otca(otca_on, otca_off, otca_pattern);
+                var names = ["otcametapixel", "otcametapixeloff", pattern_parameter];
+
+                if(names.every(name => universes[name]))
+                {
+                    load_otca(universes.otcametapixel, universes.otcametapixeloff, universes[pattern_parameter]);
+                }
+                else
+                {
+                    load_random();
+                }
+
+                for(var name in universes)
+                {
+                    universes[name].free();
+                }
             },
             function()
             {
                 // fallback to random pattern
                 load_random();
             });
-            */
         }
 
         function try_load_pattern(id)
@@ -400,6 +402,7 @@ var
                     set_text($("pattern_name"), "");
                     set_query("");
 
+                    life.save_undo_state();
                     life.clear_pattern();
                     update_hud();
 
@@ -414,6 +417,7 @@ var
                 {
                     stop(function()
                     {
+                        life.save_undo_state();
                         life.restore_rewind_state();
 
                         fit_pattern();
@@ -443,6 +447,9 @@ var
 
                         mouse_set = !life.get_bit(coords.x, coords.y);
 
+                        // one undo step for the whole stroke
+                        life.save_undo_state();
+
                         window.addEventListener("mousemove", do_field_draw, true);
                         do_field_draw(e);
                     }
@@ -607,6 +614,23 @@ var
                     return true;
                 }
 
+                if(e.ctrlKey && (chr === 90 || chr === 89))
+                {
+                    // ctrl+z undoes, ctrl+y and ctrl+shift+z redo
+                    const redo = chr === 89 || e.shiftKey;
+
+                    stop(function()
+                    {
+                        if(redo ? life.redo() : life.undo())
+                        {
+                            set_text($("label_step"), Math.pow(2, life.get_step()));
+                            drawer.redraw(life);
+                            update_hud();
+                        }
+                    });
+                    return false;
+                }
+
                 if(e.ctrlKey || e.shiftKey || e.altKey)
                 {
                     return true;
@@ -685,8 +709,7 @@ var
 
                     if(step >= 0)
                     {
-                        life.set_step(step);
-                        set_text($("label_step"), Math.pow(2, step));
+                        set_text($("label_step"), Math.pow(2, life.set_step(step)));
                     }
 
                     return false;
@@ -706,8 +729,7 @@ var
             {
                 var step = life.get_step() + 1;
 
-                life.set_step(step);
-                set_text($("label_step"), Math.pow(2, step));
+                set_text($("label_step"), Math.pow(2, life.set_step(step)));
             };
 
             $("slower_button").onclick = function()
@@ -716,8 +738,7 @@ var
                 {
                     var step = life.get_step() - 1;
 
-                    life.set_step(step);
-                    set_text($("label_step"), Math.pow(2, step));
+                    set_text($("label_step"), Math.pow(2, life.set_step(step)));
                 }
             };
 
@@ -798,6 +819,24 @@ var
 
                 function load(text)
                 {
+                    if(text.trim().startsWith("@RULE"))
+                    {
+                        // a .rule file replaces the rule of the current pattern
+                        try
+                        {
+                            life.set_rule_table(text);
+                        }
+                        catch(error)
+                        {
+                            set_text($("import_info"), "Invalid rule file, " + error);
+                            return;
+                        }
+
+                        $("import_file").value = "";
+                        hide_overlay();
+                        return;
+                    }
+
                     setup_pattern(text, undefined);
 
                     if(previous !== current_pattern.title) {
@@ -857,6 +896,7 @@ var
 
                 stop(function()
                     {
+                        life.save_undo_state();
                         life.clear_pattern();
 
                         // Note: Not exact density because some points may be repeated
@@ -891,26 +931,33 @@ var
 
             $("settings_submit").onclick = function()
             {
-                var new_rule_s,
-                    new_rule_b,
-                    new_gen_step;
+                var new_gen_step;
 
-                hide_overlay();
+                // the rule is kept as it is unless another one is entered,
+                // rule tables can only be loaded from files
+                if($("rule").value !== life.get_rule())
+                {
+                    try
+                    {
+                        life.set_rule($("rule").value);
+                    }
+                    catch(error)
+                    {
+                        set_text($("rule_info"), "Invalid rule: " + error);
+                        return;
+                    }
+                }
 
-                new_rule_s = formats.parse_rule($("rule").value, true);
-                new_rule_b = formats.parse_rule($("rule").value, false);
+                hide_overlay();
 
                 new_gen_step = Math.round(Math.log(Number($("gen_step").value) || 0) / Math.LN2);
 
-                life.set_rules(new_rule_s, new_rule_b);
-
                 if(!new_gen_step || new_gen_step < 0) {
                     life.set_step(0);
                     set_text($("label_step"), "1");
                 }
                 else {
-                    life.set_step(new_gen_step);
-                    set_text($("label_step"), Math.pow(2, new_gen_step));
+                    set_text($("label_step"), Math.pow(2, life.set_step(new_gen_step)));
                 }
 
                 max_fps = Number($("max_fps").value);
@@ -956,7 +1003,8 @@ var
             {
                 show_overlay("settings_dialog");
 
-                $("rule").value = formats.rule2str(life.get_rule_s(), life.get_rule_b());
+                $("rule").value = life.get_rule();
+                set_text($("rule_info"), "");
                 $("max_fps").value = max_fps;
                 $("gen_step").value = Math.pow(2, life.get_step());
 
@@ -1186,6 +1234,7 @@ var
         {
             result = {
                 comment: "",
+                comments: [],
                 urls: [],
                 short_comment: "",
             };
@@ -1203,6 +1252,7 @@ var
                 result.title = pattern_id;
             }
 
+            life.save_undo_state();
             life.clear_pattern();
 
             if(!is_mc)
@@ -1220,13 +1270,19 @@ var
 
             life.save_rewind_state();
 
-            if(result.rule_s && result.rule_b)
-            {
-                life.set_rules(result.rule_s, result.rule_b);
-            }
-            else
+            life.set_rules(1 << 2 | 1 << 3, 1 << 3);
+
+            if(result.rule)
             {
-                life.set_rules(1 << 2 | 1 << 3, 1 << 3);
+                try
+                {
+                    life.set_rule(result.rule);
+                }
+                catch(error)
+                {
+                    // the pattern is still loaded, with the default rule
+                    result.comment += "\nInvalid rule " + result.rule + ": " + error + "\n";
+                }
             }
 
             hide_overlay();
@@ -1251,6 +1307,15 @@ var
                 view_url = view_link(pattern_id);
             }
 
+            const info = new wasm_bindgen.PatternInfo();
+            info.name = result.title || "";
+            info.author = result.author || "";
+            info.comments = result.comments;
+            info.urls = result.urls;
+            info.source_url = pattern_source_url || "";
+            life.set_pattern_info(info);
+            info.free();
+
             current_pattern = {
                 title : result.title,
                 comment : result.comment,
@@ -1278,21 +1343,23 @@ var
     /*
      * load a pattern consisting of otca metapixels
      */
-    /*function load_otca(otca_on, otca_off, field)
+    function load_otca(otca_on, otca_off, pattern)
     {
-        var bounds = life.get_bounds(field);
-
         life.set_step(10);
+        life.set_rules(1 << 2 | 1 << 3, 1 << 3);
         max_fps = 6;
 
-        drawer.cell_width = 1 / 32;
+        life.build_metapattern(otca_on, otca_off, pattern, 11);
+        life.save_rewind_state();
 
-        life.make_center(field, bounds);
-        life.setup_meta(otca_on, otca_off, field, bounds);
+        hide_overlay();
 
-        update_hud();
+        fit_pattern();
         drawer.redraw(life);
-    }*/
+
+        update_hud();
+        set_text($("label_step"), Math.pow(2, 10));
+    }
 
     function run()
     {
@@ -1312,6 +1379,9 @@ var
             life.save_rewind_state();
         }
 
+        // a whole run is undone at once
+        life.save_undo_state();
+
         interval = setInterval(function()
         {
             update_hud(1000 / frame_time);
@@ -1324,6 +1394,7 @@ var
         {
             if(!running)
             {
+                life.cancel_step();
                 clearInterval(interval);
                 update_hud(1000 / frame_time);
 
@@ -1337,7 +1408,30 @@ var
 
             if(per_frame * n < (time - start))
             {
-                life.next_generation(true);
+                if(!life.has_pending_step())
+                {
+                    life.begin_step(true);
+                }
+
+                // slow generations are spread over several frames, so that
+                // the page stays responsive
+                var status = life.continue_step(STEP_BUDGET);
+
+                if(status === wasm_bindgen.StepStatus.OutOfMemory)
+                {
+                    stop(function()
+                    {
+                        set_text($("label_fps"), "Out of memory");
+                    });
+                }
+
+                if(status !== wasm_bindgen.StepStatus.Done)
+                {
+                    // edited patterns start a new step in the next frame
+                    nextFrame(update);
+                    return;
+                }
+
                 drawer.redraw(life);
 
                 n++;
@@ -1367,6 +1461,7 @@ var
             life.save_rewind_state();
         }
 
+        life.save_undo_state();
         life.next_generation(is_single);
         drawer.redraw(life);
 
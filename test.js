@@ -80,9 +80,17 @@ for(let file of files)
     life.make_center(pattern.field_x, pattern.field_y, bounds);
     life.setup_field(pattern.field_x, pattern.field_y, bounds);
 
-    if(pattern.rule_s && pattern.rule_b)
+    if(pattern.rule)
     {
-        life.set_rules(pattern.rule_s, pattern.rule_b);
+        try
+        {
+            life.set_rule(pattern.rule);
+        }
+        catch(error)
+        {
+            console.error("While setting the rule of %s: %s", file, error);
+            continue;
+        }
     }
 
     const expected_population = POPULATIONS_AFTER_PARSE[file];
@@ -105,11 +113,7 @@ for(let file of files)
     const new_pattern = formats.parse_pattern(generated_rle);
     console.assert(!new_pattern.error);
 
-    if(pattern.rule_s && pattern.rule_b)
-    {
-        console.assert(new_pattern.rule_s === pattern.rule_s);
-        console.assert(new_pattern.rule_b === pattern.rule_b);
-    }
+    console.assert(new_pattern.rule === life.get_rule());
 
     const new_life = new LifeUniverse();
     const new_bounds = new_life.get_bounds(new_pattern.field_x, new_pattern.field_y);
@@ -0,0 +1,255 @@
+// Goes through a range of life-like rules and tells for each how random soups
+// behave in it, as CSV with one line per rule. Every rule gets the same soups,
+// so that the rules can be compared.
+//
+// A soup dies when no cell is left, and is explosive when its population grows
+// past a multiple of the soup size. Otherwise the cells it visits in the next
+// to last MAX_PERIOD generations are its envelope. It is stable when it
+// repeats itself in place within that time or stays inside the envelope in the
+// last MAX_PERIOD generations, and has spaceships when cells escape it.
+
+use super::rule::RuleRange;
+use super::{BooleanOp, LifeUniverse, NodeId};
+use std::fmt::Write;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const SOUP_SIZE: usize = 16;
+// populations above this many times the cells of a soup are explosive
+const EXPLOSIVE_FACTOR: usize = 16;
+// generations are run in steps of 2^STEP until the last MAX_PERIOD
+const STEP: usize = 5;
+const MAX_PERIOD: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Dies,
+    Stable,
+    Spaceships,
+    Explosive,
+}
+
+// from least to most interesting, a rule is classed by the most interesting
+// of its soups
+const CLASSES: [Class; 4] = [
+    Class::Dies,
+    Class::Stable,
+    Class::Spaceships,
+    Class::Explosive,
+];
+
+impl Class {
+    fn name(self) -> &'static str {
+        match self {
+            Class::Dies => "dies",
+            Class::Stable => "stable",
+            Class::Spaceships => "spaceships",
+            Class::Explosive => "explosive",
+        }
+    }
+}
+
+// xorshift, seeded by the number of the soup
+struct Soup(u64);
+
+impl Soup {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+impl LifeUniverse {
+    // a soup of SOUP_SIZE by SOUP_SIZE cells, each alive with a chance of one
+    // half, centred on the origin
+    fn setup_soup(&mut self, seed: usize) {
+        let mut soup = Soup(0x9e37_79b9_7f4a_7c15 ^ seed as u64);
+        let half = (SOUP_SIZE / 2) as f64;
+
+        for y in 0..SOUP_SIZE {
+            for x in 0..SOUP_SIZE {
+                if soup.next() & 1 != 0 {
+                    self.set_cell_state(x as f64 - half, y as f64 - half, 1);
+                }
+            }
+        }
+    }
+
+    fn classify_soup(&mut self, generations: usize) -> Class {
+        let explosive = EXPLOSIVE_FACTOR * SOUP_SIZE * SOUP_SIZE;
+        let end = generations.saturating_sub(2 * MAX_PERIOD) as f64;
+
+        while self.generation < end {
+            // the last steps are shorter, so that the run ends exactly at end
+            let step = ((end - self.generation).log2() as usize).min(STEP);
+            self.set_step(step);
+            if !self.next_generation(true) {
+                // out of memory
+                return Class::Explosive;
+            }
+
+            let population = self.get_population();
+            if population == 0 {
+                return Class::Dies;
+            }
+            if population > explosive {
+                return Class::Explosive;
+            }
+        }
+
+        let start = self.root();
+        self.hashmap.borrow_mut().pin(start);
+        self.set_step(0);
+        let class = self.classify_periods(start);
+        self.hashmap.borrow_mut().unpin(start);
+        class
+    }
+
+    // the class of a soup from start on, one generation at a time for two
+    // periods
+    fn classify_periods(&mut self, start: NodeId) -> Class {
+        let explosive = EXPLOSIVE_FACTOR * SOUP_SIZE * SOUP_SIZE;
+        let mut envelope = start;
+        self.hashmap.borrow_mut().pin(envelope);
+
+        let mut generation = 0;
+        let class = loop {
+            generation += 1;
+            if !self.next_generation(true) {
+                break Class::Explosive;
+            }
+
+            let population = self.get_population();
+            if population == 0 {
+                break Class::Dies;
+            }
+            if population > explosive {
+                break Class::Explosive;
+            }
+
+            // both are centred on the origin, equal patterns are the same
+            // node once they have the same level
+            let level = self.node(envelope).level.max(self.node(self.root()).level);
+            let current = self.expand_to_level(self.root(), level);
+
+            if generation <= MAX_PERIOD && self.expand_to_level(start, level) == current {
+                break Class::Stable;
+            }
+
+            let expanded = self.expand_to_level(envelope, level);
+            if generation <= MAX_PERIOD {
+                // the first period: the cells the pattern visits are its envelope
+                let union = self.node_boolean(BooleanOp::Union, expanded, current);
+                self.hashmap.borrow_mut().unpin(envelope);
+                self.hashmap.borrow_mut().pin(union);
+                envelope = union;
+                continue;
+            }
+
+            // the second period: oscillators and still lifes stay inside the
+            // envelope, spaceships leave it
+            let escaped = self.node_boolean(BooleanOp::Difference, current, expanded);
+            if self.node(escaped).population != 0 {
+                break Class::Spaceships;
+            }
+            if generation == 2 * MAX_PERIOD {
+                break Class::Stable;
+            }
+        };
+
+        self.hashmap.borrow_mut().unpin(envelope);
+        class
+    }
+}
+
+#[wasm_bindgen]
+impl LifeUniverse {
+    // runs seeds soups for generations in every rule of a range like B3x/S2x
+    // or B3/S23..B36/S236, one CSV line per rule with its class and how many
+    // soups had each class. Slow for large ranges, meant for scripts rather
+    // than the UI.
+    #[allow(dead_code)]
+    pub fn classify_rules(range: &str, seeds: usize, generations: usize) -> Result<String, String> {
+        let range = RuleRange::parse(range).map_err(|error| error.to_string())?;
+        let mut life = LifeUniverse::new();
+        let mut csv = String::from("rule,class");
+
+        for class in CLASSES {
+            write!(csv, ",{}", class.name()).unwrap();
+        }
+        csv.push('\n');
+
+        for (s, b) in range.rules() {
+            life.set_rules(s, b);
+            let mut counts = [0; CLASSES.len()];
+
+            for seed in 0..seeds {
+                life.clear_pattern();
+                life.setup_soup(seed);
+                let class = life.classify_soup(generations);
+                counts[CLASSES.iter().position(|&c| c == class).unwrap()] += 1;
+            }
+
+            let most = counts.iter().rposition(|&count| count != 0);
+            let class = most.map_or("", |i| CLASSES[i].name());
+            write!(csv, "{},{class}", life.get_rule()).unwrap();
+            for count in counts {
+                write!(csv, ",{count}").unwrap();
+            }
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::load;
+
+    // the class of the rule, the second column of its line
+    fn rule_class(rule: &str) -> String {
+        let csv = LifeUniverse::classify_rules(rule, 8, 1000).unwrap();
+        let line = csv.lines().nth(1).unwrap();
+        line.split(',').nth(1).unwrap().to_string()
+    }
+
+    #[test]
+    fn rules() {
+        // some soups of life leave gliders among their ash
+        assert_eq!(rule_class("B3/S23"), "spaceships");
+        assert_eq!(rule_class("B3678/S34678"), "stable");
+        assert_eq!(rule_class("B3/S"), "dies");
+        assert_eq!(rule_class("B2/S"), "explosive");
+    }
+
+    #[test]
+    fn soups() {
+        let mut life = LifeUniverse::new();
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let block = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        let blinker = [(0, 0), (1, 0), (2, 0)];
+        let block_and_glider: Vec<_> = glider
+            .iter()
+            .map(|&(x, y)| (x + 5, y + 5))
+            .chain(block)
+            .collect();
+
+        // the coarse steps stop at 300 - 2 * MAX_PERIOD. Oscillators repeat
+        // after their period, gliders escape the envelope of the first period
+        // right after it.
+        for (cells, class, generations) in [
+            (&glider[..], Class::Spaceships, MAX_PERIOD + 1),
+            (&block[..], Class::Stable, 1),
+            (&blinker[..], Class::Stable, 2),
+            (&block_and_glider[..], Class::Spaceships, MAX_PERIOD + 1),
+        ] {
+            life.clear_pattern();
+            load(&mut life, cells);
+            assert!(life.classify_soup(300) == class);
+            assert_eq!(life.generation, (300 - 2 * MAX_PERIOD + generations) as f64);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// Minimal animated gif encoder with a 256 color palette. Frames are lzw
+// compressed as their pixels come in.
+
+use rustc_hash::FxBuildHasher;
+use std::collections::HashMap;
+
+const CLEAR: u16 = 256;
+const END: u16 = 257;
+const MAX_CODES: u16 = 4096;
+
+struct Lzw {
+    out: Vec<u8>,
+    // finished bytes not yet written as a sub-block
+    block: Vec<u8>,
+    bits: u32,
+    count: u32,
+    size: u32,
+    next: u16,
+    prefix: Option<u16>,
+    table: HashMap<(u16, u8), u16, FxBuildHasher>,
+}
+
+impl Lzw {
+    fn new() -> Lzw {
+        let mut lzw = Lzw {
+            out: Vec::new(),
+            block: Vec::with_capacity(255),
+            bits: 0,
+            count: 0,
+            size: 9,
+            next: END + 1,
+            prefix: None,
+            table: HashMap::default(),
+        };
+        lzw.emit(CLEAR);
+        lzw
+    }
+
+    fn emit(&mut self, code: u16) {
+        self.bits |= (code as u32) << self.count;
+        self.count += self.size;
+
+        while self.count >= 8 {
+            self.push_byte(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.block.push(byte);
+
+        if self.block.len() == 255 {
+            self.out.push(255);
+            self.out.append(&mut self.block);
+        }
+    }
+
+    fn write(&mut self, pixels: &[u8]) {
+        for &pixel in pixels {
+            let Some(prefix) = self.prefix else {
+                self.prefix = Some(pixel as u16);
+                continue;
+            };
+
+            if let Some(&code) = self.table.get(&(prefix, pixel)) {
+                self.prefix = Some(code);
+                continue;
+            }
+
+            self.emit(prefix);
+
+            if self.next < MAX_CODES {
+                self.table.insert((prefix, pixel), self.next);
+                self.next += 1;
+
+                // the decoder adds its entries one code later, so it widens one code later too
+                if self.next as u32 > 1 << self.size && self.size < 12 {
+                    self.size += 1;
+                }
+            } else {
+                self.emit(CLEAR);
+                self.table.clear();
+                self.size = 9;
+                self.next = END + 1;
+            }
+
+            self.prefix = Some(pixel as u16);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if let Some(prefix) = self.prefix {
+            self.emit(prefix);
+        }
+        self.emit(END);
+
+        if self.count > 0 {
+            self.push_byte(self.bits as u8);
+        }
+        if !self.block.is_empty() {
+            self.out.push(self.block.len() as u8);
+            self.out.append(&mut self.block);
+        }
+
+        self.out.push(0); // block terminator
+        self.out
+    }
+}
+
+pub struct GifEncoder {
+    out: Vec<u8>,
+    width: u16,
+    height: u16,
+    frame: Option<Lzw>,
+}
+
+impl GifEncoder {
+    // palette holds 256 rgb colors, the animation loops forever
+    pub fn new(width: u16, height: u16, palette: &[[u8; 3]; 256]) -> GifEncoder {
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.extend_from_slice(&[0xF7, 0, 0]); // global color table with 256 entries
+
+        for color in palette {
+            out.extend_from_slice(color);
+        }
+
+        out.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        GifEncoder {
+            out,
+            width,
+            height,
+            frame: None,
+        }
+    }
+
+    // plain text that isn't shown, empty text is left out
+    pub fn comment(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.out.extend_from_slice(&[0x21, 0xFE]);
+        for block in text.as_bytes().chunks(255) {
+            self.out.push(block.len() as u8);
+            self.out.extend_from_slice(block);
+        }
+        self.out.push(0);
+    }
+
+    // delay is in hundredths of a second
+    pub fn begin_frame(&mut self, delay: u16) {
+        self.end_frame();
+
+        self.out.extend_from_slice(&[0x21, 0xF9, 4, 0]);
+        self.out.extend_from_slice(&delay.to_le_bytes());
+        self.out.extend_from_slice(&[0, 0]);
+
+        self.out.push(0x2C);
+        self.out.extend_from_slice(&[0, 0, 0, 0]);
+        self.out.extend_from_slice(&self.width.to_le_bytes());
+        self.out.extend_from_slice(&self.height.to_le_bytes());
+        self.out.push(0); // no local color table
+        self.out.push(8); // lzw minimum code size
+
+        self.frame = Some(Lzw::new());
+    }
+
+    // palette indices, row by row
+    pub fn write_pixels(&mut self, pixels: &[u8]) {
+        if let Some(frame) = &mut self.frame {
+            frame.write(pixels);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.out.append(&mut frame.finish());
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.end_frame();
+        self.out.push(0x3B);
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what a decoder saw of a frame
+    struct Frame {
+        pixels: Vec<u8>,
+        delay: u16,
+        // clear codes, including the first one
+        clears: usize,
+        // every code size the decoder widened to
+        sizes: Vec<u32>,
+    }
+
+    fn decode_lzw(data: &[u8], frame: &mut Frame) {
+        let (mut position, mut size) = (0, 9);
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut previous: Option<Vec<u8>> = None;
+
+        loop {
+            let mut code = 0;
+            for i in 0..size {
+                let bit = data[(position + i) / 8] >> ((position + i) % 8) & 1;
+                code |= (bit as usize) << i;
+            }
+            position += size;
+
+            if code == CLEAR as usize {
+                table = (0..=END).map(|i| vec![i as u8]).collect();
+                size = 9;
+                previous = None;
+                frame.clears += 1;
+                continue;
+            }
+            if code == END as usize {
+                break;
+            }
+
+            let entry = match &previous {
+                _ if code < table.len() => table[code].clone(),
+                Some(previous) if code == table.len() => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+                _ => panic!("code {code} isn't in the table"),
+            };
+            frame.pixels.extend_from_slice(&entry);
+
+            if let Some(mut previous) = previous.take()
+                && table.len() < MAX_CODES as usize
+            {
+                previous.push(entry[0]);
+                table.push(previous);
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+                frame.sizes.push(size as u32);
+            }
+            previous = Some(entry);
+        }
+    }
+
+    // the comment and the frames of a gif
+    fn decode(gif: &[u8]) -> (String, Vec<Frame>) {
+        assert_eq!(&gif[..6], b"GIF89a");
+        let (mut comment, mut frames) = (String::new(), vec![]);
+        let mut delay = 0;
+        // after the screen descriptor and the color table
+        let mut i = 13 + 256 * 3;
+
+        let sub_blocks = |i: &mut usize| {
+            let mut data = vec![];
+            while gif[*i] != 0 {
+                let length = gif[*i] as usize;
+                data.extend_from_slice(&gif[*i + 1..*i + 1 + length]);
+                *i += 1 + length;
+            }
+            *i += 1;
+            data
+        };
+
+        loop {
+            match gif[i] {
+                0x21 => {
+                    let label = gif[i + 1];
+                    i += 2;
+                    let data = sub_blocks(&mut i);
+                    match label {
+                        0xFE => comment = String::from_utf8(data).unwrap(),
+                        0xF9 => delay = u16::from_le_bytes([data[1], data[2]]),
+                        _ => {}
+                    }
+                }
+                0x2C => {
+                    assert_eq!(gif[i + 10], 8, "lzw minimum code size");
+                    i += 11;
+                    let mut frame = Frame {
+                        pixels: vec![],
+                        delay,
+                        clears: 0,
+                        sizes: vec![],
+                    };
+                    decode_lzw(&sub_blocks(&mut i), &mut frame);
+                    frames.push(frame);
+                }
+                0x3B => return (comment, frames),
+                byte => panic!("unexpected block {byte:x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let palette = std::array::from_fn(|i| [i as u8; 3]);
+        let mut encoder = GifEncoder::new(200, 150, &palette);
+        encoder.comment("glider");
+
+        // noise fills the table fast and has to start over a few times, the
+        // second frame is mostly runs
+        let mut state = 1u32;
+        let noise: Vec<u8> = (0..200 * 150)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 24) as u8
+            })
+            .collect();
+        let runs: Vec<u8> = (0..200 * 150).map(|i| (i / 97 % 3) as u8).collect();
+
+        for (delay, pixels) in [(5, &noise), (7, &runs)] {
+            encoder.begin_frame(delay);
+            for row in pixels.chunks(200) {
+                encoder.write_pixels(row);
+            }
+        }
+
+        let (comment, frames) = decode(&encoder.finish());
+        assert_eq!(comment, "glider");
+        assert_eq!(frames.len(), 2);
+
+        assert!(frames[0].pixels == noise);
+        assert_eq!(frames[0].delay, 5);
+        assert!(frames[0].clears > 2);
+        assert!(frames[0].sizes.starts_with(&[10, 11, 12, 10, 11, 12]));
+
+        assert!(frames[1].pixels == runs);
+        assert_eq!(frames[1].delay, 7);
+    }
+}
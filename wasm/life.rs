@@ -1,18 +1,52 @@
 use rustc_hash::FxBuildHasher;
-use std::cell::Cell;
-use std::collections::HashMap;
-use std::mem::{self, MaybeUninit};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::num::NonZeroU32;
+use std::ops::{Index, IndexMut};
 use std::rc::Rc;
 use wasm_bindgen::prelude::wasm_bindgen;
 
+mod explore;
+mod gif;
+mod ltl;
+#[cfg(not(target_arch = "wasm32"))]
+mod parallel;
+mod png;
+mod quicklife;
+mod rule;
+mod ruletable;
+#[cfg(not(target_arch = "wasm32"))]
+mod state;
+#[cfg(test)]
+mod tests;
+
+use ltl::LargerThanLife;
+use rule::Rule;
+use ruletable::{MAX_STATES, RuleTable};
+
 #[global_allocator]
 static A: rlsf::GlobalTlsf = rlsf::GlobalTlsf::new();
 
-const INITIAL_CAPACITY: usize = 10_000;
+const DEFAULT_HISTORY_LIMIT: usize = 256;
 const MASK_LEFT: usize = 1;
 const MASK_TOP: usize = 2;
 const MASK_RIGHT: usize = 4;
 const MASK_BOTTOM: usize = 8;
+// the auto engine switches to quicklife after this many steps in a row that
+// missed the cache more than once per generation for every few living cells
+const AUTO_POOR_STEPS: usize = 4;
+const AUTO_CELLS_PER_MISS: f64 = 16.0;
+// steps with fewer cache misses than this are cheap with either engine
+const AUTO_MIN_MISSES: usize = 4096;
+// generations quicklife runs for before hashlife gets another try
+const AUTO_QUICKLIFE_GENERATIONS: f64 = 1024.0;
+// cell coordinates of larger roots don't fit into the tiles
+const QUICKLIFE_MAX_LEVEL: usize = 60;
+// quicklife goes one generation at a time, larger steps are left to hashlife
+const QUICKLIFE_MAX_STEP: usize = 10;
+// rows of exported images rendered at a time
+const BAND_HEIGHT: usize = 64;
 
 //static mut COLLISION_COUNT: i32 = 0;
 
@@ -24,849 +58,2790 @@ extern "C" {
     fn time(s: &str);
     #[wasm_bindgen(js_namespace = console)]
     fn timeEnd(s: &str);
+    #[wasm_bindgen(js_namespace = performance)]
+    fn now() -> f64;
 }
 
-#[repr(C)]
-struct TreeNodeMaybeUninit {
-    nw: MaybeUninit<Rc<TreeNodeMaybeUninit>>,
-    ne: MaybeUninit<Rc<TreeNodeMaybeUninit>>,
-    sw: MaybeUninit<Rc<TreeNodeMaybeUninit>>,
-    se: MaybeUninit<Rc<TreeNodeMaybeUninit>>,
-    population: usize,
-    level: usize,
-    cache: Cell<Option<Rc<TreeNodeMaybeUninit>>>,
-    quick_cache: Cell<Option<Rc<TreeNodeMaybeUninit>>>,
-    in_tree: Cell<bool>,
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    now()
 }
 
-#[repr(C)]
-struct TreeNode {
-    nw: Rc<TreeNode>,
-    ne: Rc<TreeNode>,
-    sw: Rc<TreeNode>,
-    se: Rc<TreeNode>,
-    population: usize,
-    level: usize,
-    cache: Cell<Option<Rc<TreeNode>>>,
-    quick_cache: Cell<Option<Rc<TreeNode>>>,
-    in_tree: Cell<bool>,
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
 }
 
-impl PartialEq for TreeNode {
-    fn eq(&self, other: &Self) -> bool {
-        core::ptr::eq(self, other)
+// the next generation of 64 cells at once, given the 64 cells in each of the
+// eight neighbouring positions
+fn next_cells(neighbours: [u64; 8], cells: u64, rule_b: usize, rule_s: usize) -> u64 {
+    fn half_add(a: u64, b: u64) -> (u64, u64) {
+        (a ^ b, a & b)
+    }
+    fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+        (a ^ b ^ c, a & b | c & (a ^ b))
     }
-}
 
-impl Eq for TreeNode {}
+    let [n0, n1, n2, n3, n4, n5, n6, n7] = neighbours;
+    let (s0, c0) = full_add(n0, n1, n2);
+    let (s1, c1) = full_add(n3, n4, n5);
+    let (s2, c2) = half_add(n6, n7);
 
-impl TreeNode {
-    pub fn new(
-        nw: Rc<TreeNode>,
-        ne: Rc<TreeNode>,
-        sw: Rc<TreeNode>,
-        se: Rc<TreeNode>,
-    ) -> Rc<TreeNode> {
-        let mut new_node = Self {
-            nw: nw,
-            ne: ne,
-            sw: sw,
-            se: se,
-            population: 0,
-            level: 0,
-            cache: Cell::new(None),
-            quick_cache: Cell::new(None),
-            in_tree: Cell::new(true),
-        };
-        new_node.level = new_node.nw.level + 1;
-        // log(format!("Creating new node with level: {} and id: {}", new_node.level, id).as_str());
-        new_node.population = new_node.nw.population
-            + new_node.ne.population
-            + new_node.sw.population
-            + new_node.se.population;
-        Rc::new(new_node)
-    }
-
-    pub fn new_leaf(population: usize) -> Rc<TreeNode> {
-        // log("Creating new leaf...");
-        let new_leaf = Rc::into_raw(Rc::new(TreeNodeMaybeUninit {
-            nw: MaybeUninit::uninit(),
-            ne: MaybeUninit::uninit(),
-            sw: MaybeUninit::uninit(),
-            se: MaybeUninit::uninit(),
-            population: population,
-            level: 0,
-            cache: Cell::new(None),
-            quick_cache: Cell::new(None),
-            in_tree: Cell::new(true),
-        })) as *mut TreeNodeMaybeUninit;
-        let new_leaf = unsafe {
-            (*new_leaf).nw = MaybeUninit::new(Rc::from_raw(new_leaf));
-            (*new_leaf).ne = MaybeUninit::new(Rc::from_raw(new_leaf));
-            (*new_leaf).sw = MaybeUninit::new(Rc::from_raw(new_leaf));
-            (*new_leaf).se = MaybeUninit::new(Rc::from_raw(new_leaf));
-
-            mem::transmute::<_, Rc<TreeNode>>(Rc::from_raw(new_leaf))
-        };
-        debug_assert!(new_leaf.get_cache() == None, "Cache is not None");
-        debug_assert!(
-            new_leaf.get_quick_cache() == None,
-            "Quick cache is not None"
-        );
-        debug_assert_eq!(new_leaf.population, population);
-        debug_assert_eq!(new_leaf.level, 0);
-        new_leaf
-    }
+    // the neighbour count as four bit planes
+    let (bit0, c3) = full_add(s0, s1, s2);
+    let (t0, d0) = full_add(c0, c1, c2);
+    let (bit1, d1) = half_add(t0, c3);
+    let (bit2, bit3) = half_add(d0, d1);
 
-    pub fn get_cache(&self) -> Option<Rc<TreeNode>> {
-        let cached = self.cache.take();
-        let ret = cached.clone();
-        self.cache.set(cached);
-        ret
-    }
+    let mut next = 0;
 
-    pub fn get_quick_cache(&self) -> Option<Rc<TreeNode>> {
-        let cached = self.quick_cache.take();
-        let ret = cached.clone();
-        self.quick_cache.set(cached);
-        ret
+    for count in 0..=8 {
+        let plane = |bit: u64, i: usize| if count >> i & 1 != 0 { bit } else { !bit };
+        let matches = plane(bit0, 0) & plane(bit1, 1) & plane(bit2, 2) & plane(bit3, 3);
+
+        if rule_b >> count & 1 != 0 {
+            next |= matches & !cells;
+        }
+        if rule_s >> count & 1 != 0 {
+            next |= matches & cells;
+        }
     }
+
+    next
 }
 
-struct Bounds {
-    left: i32,
-    right: i32,
-    top: i32,
-    bottom: i32,
+// four rows of four cells to the first four columns of four rows of an 8x8 bitboard
+fn spread_rows(bits: u64) -> u64 {
+    bits & 0xF | (bits & 0xF0) << 4 | (bits & 0xF00) << 8 | (bits & 0xF000) << 12
 }
 
-#[wasm_bindgen]
-struct LifeUniverse {
-    hashmap: HashMap<[usize; 4], Rc<TreeNode>, FxBuildHasher>,
-    empty_tree_cache: Vec<Rc<TreeNode>>,
-    level2_cache: Vec<Option<Rc<TreeNode>>>,
-    rule_b: usize,
-    rule_s: usize,
-    root: Rc<TreeNode>,
-    rewind_state: Option<Rc<TreeNode>>,
-    step: usize,
-    generation: f64,
-    false_leaf: Rc<TreeNode>,
-    true_leaf: Rc<TreeNode>,
+// one generation of an 8x8 bitboard, the cells on the border come out wrong
+fn bitboard_next(board: u64, rule_b: usize, rule_s: usize) -> u64 {
+    // no masking, bits shifted across rows only land on the border
+    let neighbours = [
+        board << 9,
+        board << 8,
+        board << 7,
+        board << 1,
+        board >> 1,
+        board >> 7,
+        board >> 8,
+        board >> 9,
+    ];
+    next_cells(neighbours, board, rule_b, rule_s)
 }
 
-#[wasm_bindgen]
-impl LifeUniverse {
-    fn get_key(
-        nw: &Rc<TreeNode>,
-        ne: &Rc<TreeNode>,
-        sw: &Rc<TreeNode>,
-        se: &Rc<TreeNode>,
-    ) -> [usize; 4] {
-        [
-            Rc::as_ptr(nw) as usize,
-            Rc::as_ptr(ne) as usize,
-            Rc::as_ptr(sw) as usize,
-            Rc::as_ptr(se) as usize,
-        ]
+// index of a node in its NodeMap. 0 is never used, so that an Option<NodeId>
+// takes no more room than the index itself.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct NodeId(NonZeroU32);
+
+impl NodeId {
+    fn index(self) -> usize {
+        self.0.get() as usize
     }
 
-    fn mark_node(node: &Rc<TreeNode>, in_tree: bool) {
-        if node.in_tree.get() != in_tree {
-            node.in_tree.set(in_tree);
-            if node.level > 1 {
-                Self::mark_node(&node.nw, in_tree);
-                Self::mark_node(&node.ne, in_tree);
-                Self::mark_node(&node.sw, in_tree);
-                Self::mark_node(&node.se, in_tree);
+    // the leaf of a cell in the given state
+    fn leaf(state: u8) -> NodeId {
+        NodeId(NonZeroU32::MIN.saturating_add(state as u32))
+    }
 
-                if let Some(cached) = node.get_cache() {
-                    Self::mark_node(&cached, in_tree);
-                }
+    // the state of a leaf
+    fn state(self) -> u8 {
+        (self.0.get() - 1) as u8
+    }
 
-                if let Some(cached) = node.get_quick_cache() {
-                    Self::mark_node(&cached, in_tree);
-                }
-            }
-        }
+    // the level 1 node of dead and living cells, given as nw | ne << 1 | sw << 2 | se << 3
+    fn level1(mask: usize) -> NodeId {
+        NodeId(NonZeroU32::new((LEVEL1_NODES + mask) as u32).expect("ids above 0"))
     }
 
-    fn reset_caches(&mut self) {
-        self.empty_tree_cache.clear();
-        self.level2_cache.fill(None);
+    // the level 2 node of 4x4 dead and living cells, row by row starting at the lowest bit
+    fn level2(bits: u64) -> NodeId {
+        NodeId(NonZeroU32::new((LEVEL2_NODES + bits as usize) as u32).expect("ids above 0"))
     }
 
-    fn garbage_collect(
-        hashmap: &mut HashMap<[usize; 4], Rc<TreeNode>, FxBuildHasher>,
-        root: &Rc<TreeNode>,
-    ) {
-        // log(format!("Garbage collecting..., current hs_size: {}, last_id: {}", self.hashmap_size, self.last_id).as_str());
-        // time("GC: reset hashmap");
+    // the cells of a level 2 node of dead and living cells, like level2 takes them
+    fn level2_bits(self) -> Option<u64> {
+        (LEVEL2_NODES..FIRST_NODE)
+            .contains(&self.index())
+            .then(|| (self.index() - LEVEL2_NODES) as u64)
+    }
 
-        Self::mark_node(&root, true);
-        hashmap.retain(|_, v| v.in_tree.get()); // caches are one level lower so no memory leak
-        Self::mark_node(&root, false); // reset mark
+    // the node with these children if it's one of the fixed nodes, which it
+    // is whenever all of its cells are dead or alive
+    fn fixed(children: [NodeId; 4]) -> Option<NodeId> {
+        if children
+            .iter()
+            .all(|&child| child == FALSE_LEAF || child == TRUE_LEAF)
+        {
+            let [nw, ne, sw, se] = children.map(|leaf| leaf.state() as usize);
+            return Some(NodeId::level1(nw | ne << 1 | sw << 2 | se << 3));
+        }
 
-        // resize if over half full
-        hashmap.reserve(
-            hashmap
-                .len()
-                .saturating_mul(2)
-                .saturating_sub(hashmap.capacity()),
-        );
-        // timeEnd("GC: reset hashmap");
+        let level1 = (LEVEL1_NODES..LEVEL2_NODES).contains(&children[0].index());
+        if level1 && children.iter().all(|&child| child.index() < LEVEL2_NODES) {
+            let [nw, ne, sw, se] = children.map(|child| {
+                let mask = (child.index() - LEVEL1_NODES) as u64;
+                mask & 3 | (mask & 12) << 2
+            });
+            return Some(NodeId::level2(nw | ne << 2 | sw << 8 | se << 10));
+        }
 
-        // log(format!("Garbage collection done..., new hs_size: {}, last_id: {}", self.hashmap_size, self.last_id).as_str());
+        None
     }
+}
 
-    fn create_tree(
-        hashmap: &mut HashMap<[usize; 4], Rc<TreeNode>, FxBuildHasher>,
-        root: &Rc<TreeNode>,
-        nw: &Rc<TreeNode>,
-        ne: &Rc<TreeNode>,
-        sw: &Rc<TreeNode>,
-        se: &Rc<TreeNode>,
-    ) -> Rc<TreeNode> {
-        debug_assert_eq!(nw.level, ne.level);
-        debug_assert_eq!(nw.level, sw.level);
-        debug_assert_eq!(nw.level, se.level);
+// leaves are the first ids, one for every state
+const FALSE_LEAF: NodeId = NodeId(NonZeroU32::MIN);
+const TRUE_LEAF: NodeId = NodeId(NonZeroU32::MIN.saturating_add(1));
+// then come the nodes of levels 1 and 2 whose cells are all dead or alive,
+// with ids given by their cells. They are never collected, so a level 3 node of
+// such cells is an 8x8 bitboard held in its four children and doesn't keep any
+// other node alive.
+const LEVEL1_NODES: usize = MAX_STATES + 1;
+const LEVEL2_NODES: usize = LEVEL1_NODES + (1 << 4);
+const FIRST_NODE: usize = LEVEL2_NODES + (1 << 16);
+// level of the slots in the arena that hold no node
+const FREE: usize = usize::MAX;
+const INITIAL_TABLE_SIZE: usize = 1 << 14;
+// nodes at which the first garbage collection between steps runs
+const INITIAL_COLLECT_NODES: usize = INITIAL_TABLE_SIZE / 4 * 3;
+// estimated bytes of the node store until set_memory_limit is called, well
+// below the 4 GiB of wasm memory
+const DEFAULT_MEMORY_LIMIT: usize = 512 << 20;
+
+// the children of a leaf are the leaf itself
+#[derive(Clone, Copy)]
+struct TreeNode {
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+    population: usize,
+    level: usize,
+    cache: Option<NodeId>,
+    quick_cache: Option<NodeId>,
+}
 
-        if hashmap.len() == hashmap.capacity() {
-            Self::garbage_collect(hashmap, root);
+impl TreeNode {
+    fn leaf(id: NodeId, population: usize) -> TreeNode {
+        TreeNode {
+            nw: id,
+            ne: id,
+            sw: id,
+            se: id,
+            population,
+            level: 0,
+            cache: None,
+            quick_cache: None,
         }
+    }
 
-        hashmap
-            .entry(Self::get_key(nw, ne, sw, se))
-            .or_insert_with(|| TreeNode::new(nw.clone(), ne.clone(), sw.clone(), se.clone()))
-            .clone()
+    fn children(&self) -> [NodeId; 4] {
+        [self.nw, self.ne, self.sw, self.se]
     }
+}
 
-    fn empty_tree<'a>(
-        empty_tree_cache: &'a mut Vec<Rc<TreeNode>>,
-        false_leaf: &Rc<TreeNode>,
-        hashmap: &mut HashMap<[usize; 4], Rc<TreeNode>, FxBuildHasher>,
-        root: &Rc<TreeNode>,
-        level: usize,
-    ) -> &'a Rc<TreeNode> {
-        for _ in empty_tree_cache.len()..=level {
-                if let Some(last) = empty_tree_cache.last() {
-                let new_node = Self::create_tree(hashmap, &root, last, last, last, last);
-                empty_tree_cache.push(new_node);
-            } else {
-                empty_tree_cache.push(false_leaf.clone());
-            }
-        }
-        &empty_tree_cache[level]
+struct NodeMap {
+    // every node by its index, slot 0 is unused and the leaves come next
+    nodes: Vec<TreeNode>,
+    // slots of collected nodes, lowest index last
+    free: Vec<NodeId>,
+    // open addressing hash table of the nodes by their children, power of two sized
+    table: Vec<Option<NodeId>>,
+    len: usize,
+    // extra roots that garbage_collect keeps alongside the universe root, with
+    // how many times each one is pinned
+    pinned: HashMap<NodeId, usize, FxBuildHasher>,
+    // results of node_boolean by operation and operands
+    booleans: HashMap<[u32; 3], NodeId, FxBuildHasher>,
+    empty_trees: Vec<NodeId>,
+    // estimated bytes for nodes and hash table, 0 means unlimited
+    memory_limit: usize,
+    // nodes at which the next garbage collection between steps runs
+    collect_at: usize,
+    out_of_memory: bool,
+    // number of garbage collections so far, unlike the stats never reset
+    collections: usize,
+    stats: Stats,
+    // rules, custom rule and step of the cached results, universes sharing
+    // the map may differ
+    cached_for: Option<(usize, usize, usize, usize)>,
+}
+
+#[derive(Default)]
+struct Stats {
+    gc_count: usize,
+    gc_time: f64,
+    cache_flushes: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+}
+
+impl Index<NodeId> for NodeMap {
+    type Output = TreeNode;
+
+    fn index(&self, id: NodeId) -> &TreeNode {
+        &self.nodes[id.index()]
     }
+}
 
-    #[allow(dead_code)]
-    pub fn clear_pattern(&mut self) {
-        self.hashmap = HashMap::with_capacity_and_hasher(INITIAL_CAPACITY, Default::default());
-        self.empty_tree_cache.clear();
-        self.level2_cache = vec![None; 0x10000];
-        self.root = Self::empty_tree(&mut self.empty_tree_cache, &self.false_leaf, &mut self.hashmap, &self.root, 3).clone();
-        self.generation = 0.0;
-        // log("Clearing pattern...");
+impl IndexMut<NodeId> for NodeMap {
+    fn index_mut(&mut self, id: NodeId) -> &mut TreeNode {
+        &mut self.nodes[id.index()]
     }
+}
 
-    #[wasm_bindgen(constructor)]
-    #[allow(dead_code)]
-    pub fn new() -> LifeUniverse {
-        // log("Starting constructor...");
-        // log("Creating object...");
-        let false_leaf = TreeNode::new_leaf(0);
-        let true_leaf = TreeNode::new_leaf(1);
-        let mut ret = LifeUniverse {
-            hashmap: HashMap::default(),
-            empty_tree_cache: vec![],
-            level2_cache: vec![],
-            root: true_leaf.clone(),
-            generation: 0.0,
-            rule_b: 1 << 3,
-            rule_s: 1 << 2 | 1 << 3,
-            rewind_state: None,
-            step: 0,
-            false_leaf: false_leaf,
-            true_leaf: true_leaf,
+impl NodeMap {
+    fn new() -> NodeMap {
+        let mut unused = TreeNode::leaf(FALSE_LEAF, 0);
+        unused.level = FREE;
+
+        // every state but 0 counts as living
+        let leaves = (0..MAX_STATES)
+            .map(|state| TreeNode::leaf(NodeId::leaf(state as u8), (state != 0) as usize));
+        let fixed = |level: usize, children: [NodeId; 4], population: usize| TreeNode {
+            nw: children[0],
+            ne: children[1],
+            sw: children[2],
+            se: children[3],
+            population,
+            level,
+            cache: None,
+            quick_cache: None,
         };
-        // log("Clearing pattern...");
-        ret.clear_pattern();
-        // log("Done clearing patter...");
-        ret
+        let level1 = (0..1 << 4).map(|mask: usize| {
+            let children = [1, 2, 4, 8].map(|bit| NodeId::leaf((mask & bit != 0) as u8));
+            fixed(1, children, mask.count_ones() as usize)
+        });
+        let level2 = (0..1 << 16).map(|bits: u64| {
+            // the 2x2 cells of each quadrant, in the order of NodeId::level1
+            let quadrant =
+                |shift: u64| NodeId::level1((bits >> shift & 3 | bits >> shift >> 2 & 12) as usize);
+            fixed(2, [0, 2, 8, 10].map(quadrant), bits.count_ones() as usize)
+        });
+
+        NodeMap {
+            nodes: [unused]
+                .into_iter()
+                .chain(leaves)
+                .chain(level1)
+                .chain(level2)
+                .collect(),
+            free: vec![],
+            table: vec![None; INITIAL_TABLE_SIZE],
+            len: 0,
+            pinned: HashMap::default(),
+            booleans: HashMap::default(),
+            empty_trees: vec![],
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            collect_at: INITIAL_COLLECT_NODES,
+            out_of_memory: false,
+            collections: 0,
+            stats: Stats::default(),
+            cached_for: None,
+        }
     }
 
-    fn pow2(x: usize) -> f64 {
-        2_f64.powi(x.try_into().unwrap_or(i32::MAX))
-    }
+    const NODE_BYTES: usize = mem::size_of::<TreeNode>();
+    const SLOT_BYTES: usize = mem::size_of::<Option<NodeId>>();
 
-    #[allow(dead_code)]
-    pub fn save_rewind_state(&mut self) {
-        self.rewind_state = Some(self.root.clone());
+    // the leaves and fixed nodes are there from the start and don't count
+    fn memory_usage(&self) -> usize {
+        (self.nodes.len() - FIRST_NODE) * Self::NODE_BYTES + self.table.len() * Self::SLOT_BYTES
     }
 
-    #[allow(dead_code)]
-    pub fn restore_rewind_state(&mut self) {
-        if let Some(rewind_state) = &self.rewind_state {
-            self.generation = 0.0;
-            self.root = rewind_state.clone();
-            Self::garbage_collect(&mut self.hashmap, &self.root);
-        }
+    fn over_limit(&self) -> bool {
+        self.memory_limit != 0 && self.memory_usage() > self.memory_limit
     }
 
-    #[allow(dead_code)]
-    pub fn has_rewind_state(&self) -> bool {
-        self.rewind_state.is_some()
+    // whether to collect garbage after a step: over the limit, or at twice
+    // the nodes the last collection left. Within a step only the limit
+    // counts, collecting there drops the intermediate results.
+    fn wants_collection(&self) -> bool {
+        self.over_limit() || self.len >= self.collect_at
     }
 
-    fn eval_mask(&self, mask: usize) -> usize {
-        let rule = if mask & 32 != 0 {
-            self.rule_s
-        } else {
-            self.rule_b
-        };
-
-        rule >> (mask & 0x757).count_ones() & 1
+    // the nodes in the arena, without the leaves
+    fn live_nodes(&self) -> impl Iterator<Item = &TreeNode> {
+        self.nodes[FIRST_NODE..].iter().filter(|n| n.level != FREE)
     }
 
-    fn level1_create(&mut self, mask: usize) -> Rc<TreeNode> {
-        let true_leaf = self.true_leaf.clone();
-        let false_leaf = self.false_leaf.clone();
-        Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            if mask & 1 != 0 {
-                &true_leaf
-            } else {
-                &false_leaf
-            },
-            if mask & 2 != 0 {
-                &true_leaf
-            } else {
-                &false_leaf
-            },
-            if mask & 4 != 0 {
-                &true_leaf
-            } else {
-                &false_leaf
-            },
-            if mask & 8 != 0 {
-                &true_leaf
-            } else {
-                &false_leaf
-            },
-        )
+    fn bucket(&self, children: [NodeId; 4]) -> usize {
+        let mut hash: u64 = 0;
+        for child in children {
+            hash = (hash.rotate_left(5) ^ child.0.get() as u64).wrapping_mul(0x517cc1b727220a95);
+        }
+        // the high bits are the well mixed ones
+        (hash >> (64 - self.table.len().trailing_zeros())) as usize
     }
 
-    fn get_level_from_bounds(&self, bounds: Vec<f64>) -> usize {
-        let mut max = 4.0;
+    // the node with the given children, or the empty slot of the table where it goes
+    fn slot(&self, children: [NodeId; 4]) -> Result<NodeId, usize> {
+        let mask = self.table.len() - 1;
+        let mut i = self.bucket(children);
 
-        for coordinate in bounds {
-            if coordinate + 1.0 > max {
-                max = coordinate + 1.0;
-            } else if -coordinate > max {
-                max = -coordinate;
+        while let Some(id) = self.table[i] {
+            if self[id].children() == children {
+                return Ok(id);
             }
+            i = (i + 1) & mask;
         }
-
-        max.log2().ceil() as usize + 1
+        Err(i)
     }
 
-    fn node_set_bit(&mut self, node: &Rc<TreeNode>, x: f64, y: f64, living: bool) -> Rc<TreeNode> {
-        if node.level == 0 {
-            return if living {
-                self.true_leaf.clone()
-            } else {
-                self.false_leaf.clone()
-            };
+    fn create_tree(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        debug_assert_eq!(self[nw].level, self[ne].level);
+        debug_assert_eq!(self[nw].level, self[sw].level);
+        debug_assert_eq!(self[nw].level, self[se].level);
+
+        let children = [nw, ne, sw, se];
+        if let Some(id) = NodeId::fixed(children) {
+            return id;
         }
 
-        let offset = if node.level == 1 {
-            0.0
-        } else {
-            Self::pow2(node.level - 2)
+        if (self.len + 1) * 4 > self.table.len() * 3 {
+            self.rebuild_table(self.table.len() * 2);
+        }
+
+        let i = match self.slot(children) {
+            Ok(id) => return id,
+            Err(i) => i,
+        };
+
+        let node = TreeNode {
+            nw,
+            ne,
+            sw,
+            se,
+            population: self[nw].population
+                + self[ne].population
+                + self[sw].population
+                + self[se].population,
+            level: self[nw].level + 1,
+            cache: None,
+            quick_cache: None,
         };
 
-        let changed: Rc<TreeNode>;
-        let mut nw = &node.nw;
-        let mut ne = &node.ne;
-        let mut sw = &node.sw;
-        let mut se = &node.se;
+        let id = match self.free.pop() {
+            Some(id) => {
+                self[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                let index = u32::try_from(self.nodes.len() - 1).expect("fewer than 2^32 nodes");
+                NodeId(NonZeroU32::new(index).expect("slot 0 is unused"))
+            }
+        };
 
-        if x < 0.0 {
-            if y < 0.0 {
-                changed = self.node_set_bit(&nw, x + offset, y + offset, living);
-                nw = &changed;
-            } else {
-                changed = self.node_set_bit(&sw, x + offset, y - offset, living);
-                sw = &changed;
+        self.table[i] = Some(id);
+        self.len += 1;
+        id
+    }
+
+    fn rebuild_table(&mut self, size: usize) {
+        self.table = vec![None; size];
+        let mask = size - 1;
+
+        for index in FIRST_NODE..self.nodes.len() {
+            let node = &self.nodes[index];
+            if node.level == FREE {
+                continue;
             }
-        } else {
-            if y < 0.0 {
-                changed = self.node_set_bit(&ne, x - offset, y + offset, living);
-                ne = &changed;
-            } else {
-                changed = self.node_set_bit(&se, x - offset, y - offset, living);
-                se = &changed;
+
+            let mut i = self.bucket(node.children());
+            while self.table[i].is_some() {
+                i = (i + 1) & mask;
             }
+            self.table[i] = Some(NodeId(
+                NonZeroU32::new(index as u32).expect("index above 0"),
+            ));
+        }
+    }
+
+    fn empty_tree(&mut self, level: usize) -> NodeId {
+        for _ in self.empty_trees.len()..=level {
+            let tree = match self.empty_trees.last() {
+                Some(&last) => self.create_tree(last, last, last, last),
+                None => FALSE_LEAF,
+            };
+            self.empty_trees.push(tree);
         }
+        self.empty_trees[level]
+    }
 
-        Self::create_tree(&mut self.hashmap, &self.root, nw, ne, sw, se)
+    fn flush_caches(&mut self) {
+        self.uncache(true);
+        self.stats.cache_flushes += 1;
     }
 
-    fn node_get_bit(&self, node: &Rc<TreeNode>, x: f64, y: f64) -> bool {
-        if node.population == 0 {
-            return false;
+    fn uncache(&mut self, also_quick: bool) {
+        for n in &mut self.nodes {
+            n.cache = None;
+            if also_quick {
+                n.quick_cache = None;
+            }
         }
-        if node.level == 0 {
-            // other level 0 case handled above
-            return true;
+    }
+
+    fn pin(&mut self, node: NodeId) {
+        *self.pinned.entry(node).or_default() += 1;
+    }
+
+    fn unpin(&mut self, node: NodeId) {
+        if let Some(count) = self.pinned.get_mut(&node) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned.remove(&node);
+            }
         }
+    }
 
-        let offset = if node.level == 1 {
-            0.0
-        } else {
-            Self::pow2(node.level - 2)
-        };
+    // frees every node that can't be reached from the pinned nodes or the
+    // given roots. Cached results are followed too, they are one level lower
+    // so this doesn't keep whole histories alive.
+    fn collect_unreachable(&mut self, roots: &[NodeId]) {
+        self.booleans.clear();
+        self.empty_trees.clear();
+
+        let mut marked = vec![false; self.nodes.len()];
+        marked[..FIRST_NODE].fill(true);
+        let mut stack: Vec<NodeId> = self.pinned.keys().chain(roots).copied().collect();
+        // the fixed nodes stay, but their results may not be fixed
+        for node in &self.nodes[LEVEL1_NODES..FIRST_NODE] {
+            stack.extend(node.cache);
+            stack.extend(node.quick_cache);
+        }
 
-        if x < 0.0 {
-            if y < 0.0 {
-                self.node_get_bit(&node.nw, x + offset, y + offset)
-            } else {
-                self.node_get_bit(&node.sw, x + offset, y - offset)
+        while let Some(id) = stack.pop() {
+            if !marked[id.index()] {
+                marked[id.index()] = true;
+                let node = &self[id];
+                stack.extend(node.children());
+                stack.extend(node.cache);
+                stack.extend(node.quick_cache);
             }
-        } else {
-            if y < 0.0 {
-                self.node_get_bit(&node.ne, x - offset, y + offset)
+        }
+
+        // freed slots at the end are given back, the others are reused lowest first
+        while self.nodes.len() > FIRST_NODE && !marked[self.nodes.len() - 1] {
+            self.nodes.pop();
+        }
+        self.free.clear();
+        self.len = 0;
+
+        for index in (FIRST_NODE..self.nodes.len()).rev() {
+            if marked[index] {
+                self.len += 1;
             } else {
-                self.node_get_bit(&node.se, x - offset, y - offset)
+                self.nodes[index].level = FREE;
+                self.nodes[index].cache = None;
+                self.nodes[index].quick_cache = None;
+                self.free.push(NodeId(
+                    NonZeroU32::new(index as u32).expect("index above 0"),
+                ));
             }
         }
+
+        // room to double before the table grows again
+        let size = (self.len * 2).next_power_of_two().max(INITIAL_TABLE_SIZE);
+        self.rebuild_table(size);
+        self.collections += 1;
     }
 
-    #[allow(dead_code)]
-    pub fn set_bit(&mut self, x: f64, y: f64, living: bool) {
-        // log(format!("Setting bit at x: {}, y: {}, living: {}", x, y, living).as_str());
-        let level = self.get_level_from_bounds(vec![x, y]);
+    // only safe where every node still in use is pinned or one of the roots
+    fn garbage_collect(&mut self, roots: &[NodeId]) {
+        // log(format!("Garbage collecting..., current hs_size: {}, last_id: {}", self.hashmap_size, self.last_id).as_str());
+        // time("GC: reset hashmap");
+        let start = now_ms();
 
-        if living {
-            while level > self.root.level {
-                self.root = self.expand_universe(self.root.clone());
-            }
-        } else if level > self.root.level {
-            // no need to delete pixels outside of the universe
-            return;
+        self.collect_unreachable(roots);
+
+        if self.memory_limit != 0 && self.memory_usage() * 2 > self.memory_limit {
+            // less than half of the memory left: drop the cached results like
+            // golly does, which makes most of the intermediate nodes unreachable
+            self.flush_caches();
+            self.collect_unreachable(roots);
         }
 
-        self.root = self.node_set_bit(&self.root.clone(), x, y, living);
+        // the map only grows when less than half of it was freed
+        self.collect_at = (self.len * 2).max(INITIAL_COLLECT_NODES);
+        self.out_of_memory = self.over_limit();
+        self.stats.gc_count += 1;
+        self.stats.gc_time += now_ms() - start;
+        // timeEnd("GC: reset hashmap");
     }
+}
 
-    #[allow(dead_code)]
-    pub fn get_bit(&self, x: f64, y: f64) -> bool {
-        let level = self.get_level_from_bounds(vec![x, y]);
+// a rule that replaces the life-like rule given by rule_s and rule_b
+#[derive(Clone)]
+enum CustomRule {
+    Table(Rc<RuleTable>),
+    LargerThanLife(Rc<LargerThanLife>),
+}
 
-        if level > self.root.level {
-            return false;
+impl CustomRule {
+    fn id(&self) -> usize {
+        match self {
+            CustomRule::Table(table) => table.id,
+            CustomRule::LargerThanLife(rule) => rule.id,
         }
+    }
 
-        self.node_get_bit(&self.root, x, y)
+    fn name(&self) -> String {
+        match self {
+            CustomRule::Table(table) => table.name.clone(),
+            CustomRule::LargerThanLife(rule) => rule.name(),
+        }
     }
 
-    fn node_get_boundary(
-        &self,
-        node: &Rc<TreeNode>,
-        left: f64,
-        top: f64,
-        find_mask: usize,
-        boundary: &mut Vec<f64>,
-    ) {
-        if node.population == 0 || find_mask == 0 {
-            return;
+    fn states(&self) -> usize {
+        match self {
+            CustomRule::Table(table) => table.states,
+            CustomRule::LargerThanLife(rule) => rule.states,
         }
+    }
+}
 
-        if node.level == 0 {
-            if left < boundary[0] {
-                boundary[0] = left;
+#[derive(Clone)]
+struct HistoryEntry {
+    root: NodeId,
+    generation: f64,
+    rule_s: usize,
+    rule_b: usize,
+    custom_rule: Option<CustomRule>,
+    step: usize,
+}
+
+// per cell history, recorded one generation at a time
+struct CellHistory {
+    // every cell that has been alive since recording started
+    envelope: NodeId,
+    // generation of the last birth or death of each cell, as a quadtree lined
+    // up with the envelope. The root is the first node, freed nodes are reused.
+    changes: Vec<Change>,
+    free: Vec<u32>,
+    // of the envelope and the root of changes
+    level: usize,
+    start: f64,
+}
+
+// a square of the cell history
+#[derive(Clone, Copy)]
+enum Change {
+    // every cell of the square last changed in this generation, or hasn't
+    // changed since recording started if it's the start
+    At(f64),
+    // the quadrants, nw, ne, sw and se
+    Split([u32; 4]),
+}
+
+impl CellHistory {
+    fn new(envelope: NodeId, level: usize, start: f64) -> CellHistory {
+        CellHistory {
+            envelope,
+            changes: vec![Change::At(start)],
+            free: vec![],
+            level,
+            start,
+        }
+    }
+
+    fn add(&mut self, change: Change) -> u32 {
+        match self.free.pop() {
+            Some(index) => {
+                self.changes[index as usize] = change;
+                index
             }
-            if left > boundary[1] {
-                boundary[1] = left;
+            None => {
+                self.changes.push(change);
+                self.changes.len() as u32 - 1
             }
+        }
+    }
 
-            if top < boundary[2] {
-                boundary[2] = top;
+    fn split(&mut self, index: u32) -> [u32; 4] {
+        match self.changes[index as usize] {
+            Change::Split(quadrants) => quadrants,
+            Change::At(generation) => {
+                let quadrants = [(); 4].map(|_| self.add(Change::At(generation)));
+                self.changes[index as usize] = Change::Split(quadrants);
+                quadrants
             }
-            if top > boundary[3] {
-                boundary[3] = top;
-            }
-        } else {
-            let offset = Self::pow2(node.level - 1);
+        }
+    }
 
-            if left >= boundary[0]
-                && left + offset * 2.0 <= boundary[1]
-                && top >= boundary[2]
-                && top + offset * 2.0 <= boundary[3]
+    // grows the tree around its centre, like expand_to_level does with nodes
+    fn expand_to_level(&mut self, level: usize) {
+        while self.level < level {
+            self.level += 1;
+
+            if let Change::At(generation) = self.changes[0]
+                && generation == self.start
+            {
+                continue;
+            }
+
+            // the old quadrants become the inner corners of the new ones
+            let inner = self.split(0);
+            let quadrants = [3, 2, 1, 0].map(|corner| {
+                let parts = std::array::from_fn(|i| match i == corner {
+                    true => inner[3 - corner],
+                    false => self.add(Change::At(self.start)),
+                });
+                self.add(Change::Split(parts))
+            });
+            self.changes[0] = Change::Split(quadrants);
+        }
+    }
+
+    // sets the cells that differ between two nodes lined up with the history
+    // to the given generation
+    fn record(&mut self, hashmap: &NodeMap, before: NodeId, after: NodeId, generation: f64) {
+        // false once the quadrants of the square are done, so that they can be merged
+        let mut stack = vec![(before, after, 0, true)];
+
+        while let Some((before, after, index, descend)) = stack.pop() {
+            if !descend {
+                let Change::Split(quadrants) = self.changes[index as usize] else {
+                    continue;
+                };
+                let generations = quadrants.map(|quadrant| match self.changes[quadrant as usize] {
+                    Change::At(generation) => Some(generation),
+                    Change::Split(_) => None,
+                });
+                if generations[0].is_some() && generations.iter().all(|&g| g == generations[0]) {
+                    self.changes[index as usize] = self.changes[quadrants[0] as usize];
+                    self.free.extend(quadrants);
+                }
+                continue;
+            }
+
+            let (b, a) = (&hashmap[before], &hashmap[after]);
+            if before == after || b.population == 0 && a.population == 0 {
+                continue;
+            }
+
+            if b.level == 0 {
+                self.changes[index as usize] = Change::At(generation);
+                continue;
+            }
+
+            let quadrants = self.split(index);
+            stack.push((before, after, index, false));
+            stack.push((b.nw, a.nw, quadrants[0], true));
+            stack.push((b.ne, a.ne, quadrants[1], true));
+            stack.push((b.sw, a.sw, quadrants[2], true));
+            stack.push((b.se, a.se, quadrants[3], true));
+        }
+    }
+
+    // the generation in which the cell at x, y last changed, found like
+    // node_get_cell does
+    fn last_change(&self, mut x: f64, mut y: f64) -> f64 {
+        let half = LifeUniverse::pow2(self.level - 1);
+        if !(-half..half).contains(&x) || !(-half..half).contains(&y) {
+            return self.start;
+        }
+
+        let (mut index, mut level) = (0, self.level);
+        loop {
+            let quadrants = match self.changes[index as usize] {
+                Change::At(generation) => return generation,
+                Change::Split(quadrants) => quadrants,
+            };
+
+            let offset = if level == 1 {
+                0.0
+            } else {
+                LifeUniverse::pow2(level - 2)
+            };
+            let (east, south) = (x >= 0.0, y >= 0.0);
+            x += if east { -offset } else { offset };
+            y += if south { -offset } else { offset };
+            index = quadrants[east as usize + 2 * south as usize];
+            level -= 1;
+        }
+    }
+}
+
+// one level of the hashlife recursion, kept on an explicit stack instead of the
+// call stack, which is small on wasm
+struct StepFrame {
+    node: NodeId,
+    quick: bool,
+    // the nine overlapping subnodes followed by the results of the four quadrants
+    parts: [Option<NodeId>; 13],
+    len: usize,
+}
+
+impl StepFrame {
+    fn new(node: NodeId, quick: bool) -> StepFrame {
+        StepFrame {
+            node,
+            quick,
+            parts: Default::default(),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, node: NodeId) {
+        self.parts[self.len] = Some(node);
+        self.len += 1;
+    }
+
+    fn part(&self, i: usize) -> NodeId {
+        self.parts[i].expect("part is computed")
+    }
+
+    // the nodes the frame still needs
+    fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::once(self.node).chain(self.parts[..self.len].iter().flatten().copied())
+    }
+}
+
+enum StepAction {
+    Push(NodeId, bool),
+    Return(NodeId),
+}
+
+// a step computed a bit at a time by continue_step
+struct PendingStep {
+    // the root the step started from, the result is dropped if it changed meanwhile
+    from: NodeId,
+    is_single: bool,
+    stack: Vec<StepFrame>,
+    result: Option<NodeId>,
+    // the garbage collections of the store so far, any other one may have
+    // freed the nodes of the stack
+    collections: usize,
+    // cache misses before the step, for the auto engine
+    misses: usize,
+    // done by quicklife in one go rather than a bit at a time
+    quicklife: bool,
+}
+
+// render_rgba and render_density draw into exactly width * height pixels
+fn check_buffer(length: usize, width: usize, height: usize) -> Result<(), String> {
+    if Some(length) == width.checked_mul(height) {
+        Ok(())
+    } else {
+        Err(format!(
+            "a buffer of {width}x{height} pixels can't have {length} pixels"
+        ))
+    }
+}
+
+// a png written a band at a time by continue_png
+struct PendingPng {
+    // the pattern of the image, pinned until the png is done
+    root: NodeId,
+    encoder: png::PngEncoder,
+    left: f64,
+    top: f64,
+    scale: f64,
+    image_width: usize,
+    image_height: usize,
+    cell_color: u32,
+    background: u32,
+    // rows written so far
+    rows: usize,
+}
+
+// position of the root node on the canvas, same as the arguments of draw
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+struct View {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+#[wasm_bindgen]
+impl View {
+    #[wasm_bindgen(constructor)]
+    #[allow(dead_code)]
+    pub fn new(x: f64, y: f64, size: f64, offset_x: f64, offset_y: f64) -> View {
+        View {
+            x,
+            y,
+            size,
+            offset_x,
+            offset_y,
+        }
+    }
+}
+
+// where a pattern came from, filled in by the importers and written back out
+// by the exporters
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Default)]
+struct PatternInfo {
+    pub name: String,
+    pub author: String,
+    // one entry per line
+    pub comments: Vec<String>,
+    // links found in the comments
+    pub urls: Vec<String>,
+    // where the pattern was loaded from
+    pub source_url: String,
+}
+
+#[wasm_bindgen]
+impl PatternInfo {
+    #[wasm_bindgen(constructor)]
+    #[allow(dead_code)]
+    pub fn new() -> PatternInfo {
+        PatternInfo::default()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    Intersect,
+    Difference,
+    Xor,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Hashlife,
+    QuickLife,
+    // hashlife, or quicklife while hashlife finds few cached results
+    Auto,
+}
+
+// what a call to continue_step did
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    // the step isn't done, continue_step has to be called again
+    Working,
+    // the universe advanced by the step
+    Done,
+    // the memory limit was reached, the step was dropped
+    OutOfMemory,
+    // the pattern was changed after begin_step, the step was dropped
+    Edited,
+    // there was no step to work on
+    Idle,
+}
+
+// nodes shared by any number of universes, so that identical patterns in
+// them are stored once and their results computed once
+#[wasm_bindgen]
+struct NodeStore {
+    nodes: Rc<RefCell<NodeMap>>,
+}
+
+#[wasm_bindgen]
+impl NodeStore {
+    #[wasm_bindgen(constructor)]
+    #[allow(dead_code)]
+    pub fn new() -> NodeStore {
+        NodeStore {
+            nodes: Rc::new(RefCell::new(NodeMap::new())),
+        }
+    }
+}
+
+struct Bounds {
+    left: i32,
+    right: i32,
+    top: i32,
+    bottom: i32,
+}
+
+#[wasm_bindgen]
+struct LifeUniverse {
+    hashmap: Rc<RefCell<NodeMap>>,
+    rule_b: usize,
+    rule_s: usize,
+    // replaces the rule given by rule_b and rule_s
+    custom_rule: Option<CustomRule>,
+    root: Cell<NodeId>,
+    rewind_state: Option<NodeId>,
+    undo_stack: VecDeque<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+    history_limit: usize,
+    snapshots: Vec<(String, HistoryEntry)>,
+    info: PatternInfo,
+    cell_history: Option<CellHistory>,
+    pending_step: Option<PendingStep>,
+    pending_png: Option<PendingPng>,
+    // reused by every step of a larger than life rule
+    ltl_scratch: ltl::Scratch,
+    // threads that step large nodes
+    #[cfg(not(target_arch = "wasm32"))]
+    threads: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    store_results: parallel::StoreResults,
+    engine: Engine,
+    // the quicklife tiles, dropped whenever the root is replaced
+    tiles: Option<quicklife::QuickLife>,
+    // the root is older than the tiles and is built from them when needed
+    root_behind: Cell<bool>,
+    poor_steps: usize,
+    quicklife_until: f64,
+    step: usize,
+    generation: f64,
+}
+
+#[wasm_bindgen]
+impl LifeUniverse {
+    fn node(&self, id: NodeId) -> TreeNode {
+        self.hashmap.borrow()[id]
+    }
+
+    fn create_tree(&self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        self.hashmap.borrow_mut().create_tree(nw, ne, sw, se)
+    }
+
+    fn empty_tree(&self, level: usize) -> NodeId {
+        self.hashmap.borrow_mut().empty_tree(level)
+    }
+
+    #[allow(dead_code)]
+    pub fn clear_pattern(&mut self) {
+        let root = self.empty_tree(3);
+        self.set_root(root);
+
+        // other universes may still use the nodes of a shared store
+        if !self.is_store_shared() {
+            let mut hashmap = self.hashmap.borrow_mut();
+            hashmap.uncache(true);
+            hashmap.collect_unreachable(&[]);
+        }
+        self.hashmap.borrow_mut().out_of_memory = false;
+        self.generation = 0.0;
+        self.poor_steps = 0;
+        self.quicklife_until = 0.0;
+        self.info = PatternInfo::default();
+
+        if self.cell_history.is_some() {
+            self.start_cell_history();
+        }
+        // log("Clearing pattern...");
+    }
+
+    #[wasm_bindgen(constructor)]
+    #[allow(dead_code)]
+    pub fn new() -> LifeUniverse {
+        Self::with_node_map(Rc::new(RefCell::new(NodeMap::new())))
+    }
+
+    #[allow(dead_code)]
+    pub fn with_store(store: &NodeStore) -> LifeUniverse {
+        Self::with_node_map(store.nodes.clone())
+    }
+
+    fn with_node_map(hashmap: Rc<RefCell<NodeMap>>) -> LifeUniverse {
+        // log("Starting constructor...");
+        // log("Creating object...");
+        let mut ret = LifeUniverse {
+            hashmap,
+            root: Cell::new(TRUE_LEAF),
+            generation: 0.0,
+            rule_b: 1 << 3,
+            rule_s: 1 << 2 | 1 << 3,
+            custom_rule: None,
+            rewind_state: None,
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            snapshots: vec![],
+            info: PatternInfo::default(),
+            cell_history: None,
+            pending_step: None,
+            pending_png: None,
+            ltl_scratch: ltl::Scratch::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            threads: 1,
+            #[cfg(not(target_arch = "wasm32"))]
+            store_results: Default::default(),
+            engine: Engine::Hashlife,
+            tiles: None,
+            root_behind: Cell::new(false),
+            poor_steps: 0,
+            quicklife_until: 0.0,
+            step: 0,
+        };
+        // log("Clearing pattern...");
+        ret.clear_pattern();
+        // log("Done clearing patter...");
+        ret
+    }
+
+    // a store for new universes that share their nodes with this one
+    #[allow(dead_code)]
+    pub fn get_store(&self) -> NodeStore {
+        NodeStore {
+            nodes: self.hashmap.clone(),
+        }
+    }
+
+    fn is_store_shared(&self) -> bool {
+        Rc::strong_count(&self.hashmap) > 1
+    }
+
+    fn set_root(&mut self, root: NodeId) {
+        self.tiles = None;
+        self.root_behind.set(false);
+        self.replace_root(root);
+    }
+
+    // the root is pinned, so that other universes sharing the store don't collect it
+    fn replace_root(&self, root: NodeId) {
+        let mut hashmap = self.hashmap.borrow_mut();
+        hashmap.unpin(self.root.get());
+        hashmap.pin(root);
+        self.root.set(root);
+    }
+
+    // the root, built from the quicklife tiles first if they are ahead of it
+    fn root(&self) -> NodeId {
+        if self.root_behind.get()
+            && let Some(quicklife) = &self.tiles
+        {
+            let root = self
+                .root_from_tiles(quicklife)
+                .expect("quicklife stops before the tiles outgrow a root");
+            self.replace_root(root);
+            self.root_behind.set(false);
+        }
+        self.root.get()
+    }
+
+    // what the cached results depend on: the rules, the custom rule and the step
+    fn cache_key(&self) -> (usize, usize, usize, usize) {
+        let custom = self.custom_rule.as_ref().map_or(0, CustomRule::id);
+        (self.rule_s, self.rule_b, custom, self.step)
+    }
+
+    // level of the nodes that are stepped without recursion
+    fn base_level(&self) -> usize {
+        match &self.custom_rule {
+            Some(CustomRule::LargerThanLife(rule)) => rule.base_level(),
+            _ => 2,
+        }
+    }
+
+    // results cached by another rule or step are flushed before stepping
+    fn use_caches(&mut self) {
+        let key = self.cache_key();
+        let mut hashmap = self.hashmap.borrow_mut();
+
+        match hashmap.cached_for {
+            Some((s, b, table, step)) if (s, b, table) == (key.0, key.1, key.2) => {
+                if step != self.step {
+                    hashmap.uncache(false);
+                }
+            }
+            Some(_) => hashmap.uncache(true),
+            None => {}
+        }
+
+        hashmap.cached_for = Some(key);
+    }
+
+    fn pow2(x: usize) -> f64 {
+        2_f64.powi(x.try_into().unwrap_or(i32::MAX))
+    }
+
+    #[allow(dead_code)]
+    pub fn save_rewind_state(&mut self) {
+        let root = self.root();
+        let mut hashmap = self.hashmap.borrow_mut();
+        if let Some(old) = self.rewind_state.replace(root) {
+            hashmap.unpin(old);
+        }
+        hashmap.pin(root);
+    }
+
+    #[allow(dead_code)]
+    pub fn restore_rewind_state(&mut self) {
+        if let Some(rewind_state) = self.rewind_state {
+            self.generation = 0.0;
+            self.set_root(rewind_state);
+            self.hashmap.borrow_mut().garbage_collect(&[]);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn has_rewind_state(&self) -> bool {
+        self.rewind_state.is_some()
+    }
+
+    fn history_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            root: self.root(),
+            generation: self.generation,
+            rule_s: self.rule_s,
+            rule_b: self.rule_b,
+            custom_rule: self.custom_rule.clone(),
+            step: self.step,
+        }
+    }
+
+    fn restore_history_entry(&mut self, entry: &HistoryEntry) {
+        self.set_root(entry.root);
+        self.generation = entry.generation;
+        self.set_rules(entry.rule_s, entry.rule_b);
+        self.set_custom_rule(entry.custom_rule.clone());
+        self.set_step(entry.step);
+    }
+
+    #[allow(dead_code)]
+    pub fn save_undo_state(&mut self) {
+        let entry = self.history_entry();
+        self.hashmap.borrow_mut().pin(entry.root);
+        self.undo_stack.push_back(entry);
+
+        // drop the oldest entries once the limit is reached
+        while self.undo_stack.len() > self.history_limit {
+            if let Some(oldest) = self.undo_stack.pop_front() {
+                self.hashmap.borrow_mut().unpin(oldest.root);
+            }
+        }
+
+        for entry in self.redo_stack.drain(..) {
+            self.hashmap.borrow_mut().unpin(entry.root);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn undo(&mut self) -> bool {
+        if let Some(entry) = self.undo_stack.pop_back() {
+            let current = self.history_entry();
+            self.hashmap.borrow_mut().pin(current.root);
+            self.redo_stack.push(current);
+            self.restore_history_entry(&entry);
+            self.hashmap.borrow_mut().unpin(entry.root);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn redo(&mut self) -> bool {
+        if let Some(entry) = self.redo_stack.pop() {
+            let current = self.history_entry();
+            self.hashmap.borrow_mut().pin(current.root);
+            self.undo_stack.push_back(current);
+            self.restore_history_entry(&entry);
+            self.hashmap.borrow_mut().unpin(entry.root);
+            true
+        } else {
+            false
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    #[allow(dead_code)]
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    #[allow(dead_code)]
+    pub fn clear_history(&mut self) {
+        for entry in self.undo_stack.drain(..).chain(self.redo_stack.drain(..)) {
+            self.hashmap.borrow_mut().unpin(entry.root);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+
+        while self.undo_stack.len() > self.history_limit {
+            if let Some(oldest) = self.undo_stack.pop_front() {
+                self.hashmap.borrow_mut().unpin(oldest.root);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn snapshot(&mut self, name: String) {
+        let entry = self.history_entry();
+        self.hashmap.borrow_mut().pin(entry.root);
+
+        if let Some((_, old)) = self.snapshots.iter_mut().find(|(n, _)| *n == name) {
+            let old = mem::replace(old, entry);
+            self.hashmap.borrow_mut().unpin(old.root);
+        } else {
+            self.snapshots.push((name, entry));
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn restore(&mut self, name: &str) -> bool {
+        let Some(i) = self.snapshots.iter().position(|(n, _)| n == name) else {
+            return false;
+        };
+        let entry = self.snapshots[i].1.clone();
+        self.restore_history_entry(&entry);
+        true
+    }
+
+    #[allow(dead_code)]
+    pub fn list_snapshots(&self) -> Vec<String> {
+        self.snapshots.iter().map(|(n, _)| n.clone()).collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn delete_snapshot(&mut self, name: &str) -> bool {
+        if let Some(i) = self.snapshots.iter().position(|(n, _)| n == name) {
+            let (_, entry) = self.snapshots.remove(i);
+            self.hashmap.borrow_mut().unpin(entry.root);
+            true
+        } else {
+            false
+        }
+    }
+
+    // kept until the pattern is cleared
+    #[allow(dead_code)]
+    pub fn set_pattern_info(&mut self, info: &PatternInfo) {
+        self.info = info.clone();
+    }
+
+    #[allow(dead_code)]
+    pub fn get_pattern_info(&self) -> PatternInfo {
+        self.info.clone()
+    }
+
+    // the pattern info as text, for formats that only have plain comments
+    fn info_text(&self) -> String {
+        let info = &self.info;
+        let mut lines = vec![];
+
+        if !info.name.is_empty() {
+            lines.push(info.name.clone());
+        }
+        if !info.author.is_empty() {
+            lines.push(format!("Author: {}", info.author));
+        }
+        lines.extend(info.comments.iter().cloned());
+        lines.extend(self.info_urls());
+
+        lines.join("\n")
+    }
+
+    // the links of the comments and the source, without duplicates
+    fn info_urls(&self) -> Vec<String> {
+        let mut urls = self.info.urls.clone();
+        if !self.info.source_url.is_empty() && !urls.contains(&self.info.source_url) {
+            urls.push(self.info.source_url.clone());
+        }
+        urls
+    }
+
+    fn eval_mask(&self, mask: usize) -> usize {
+        let rule = if mask & 32 != 0 {
+            self.rule_s
+        } else {
+            self.rule_b
+        };
+
+        rule >> (mask & 0x757).count_ones() & 1
+    }
+
+    fn level1_create(&self, mask: usize) -> NodeId {
+        let leaf = |bit: usize| {
+            if mask & bit != 0 {
+                TRUE_LEAF
+            } else {
+                FALSE_LEAF
+            }
+        };
+        self.create_tree(leaf(1), leaf(2), leaf(4), leaf(8))
+    }
+
+    fn get_level_from_bounds(&self, bounds: Vec<f64>) -> usize {
+        let mut max = 4.0;
+
+        for coordinate in bounds {
+            if coordinate + 1.0 > max {
+                max = coordinate + 1.0;
+            } else if -coordinate > max {
+                max = -coordinate;
+            }
+        }
+
+        max.log2().ceil() as usize + 1
+    }
+
+    fn node_set_cell(&mut self, node: NodeId, x: f64, y: f64, leaf: NodeId) -> NodeId {
+        let TreeNode {
+            mut nw,
+            mut ne,
+            mut sw,
+            mut se,
+            level,
+            ..
+        } = self.node(node);
+
+        if level == 0 {
+            return leaf;
+        }
+
+        let offset = if level == 1 {
+            0.0
+        } else {
+            Self::pow2(level - 2)
+        };
+
+        if x < 0.0 {
+            if y < 0.0 {
+                nw = self.node_set_cell(nw, x + offset, y + offset, leaf);
+            } else {
+                sw = self.node_set_cell(sw, x + offset, y - offset, leaf);
+            }
+        } else {
+            if y < 0.0 {
+                ne = self.node_set_cell(ne, x - offset, y + offset, leaf);
+            } else {
+                se = self.node_set_cell(se, x - offset, y - offset, leaf);
+            }
+        }
+
+        self.create_tree(nw, ne, sw, se)
+    }
+
+    // the leaf of the cell
+    fn node_get_cell(hashmap: &NodeMap, node: NodeId, x: f64, y: f64) -> NodeId {
+        let id = node;
+        let node = &hashmap[node];
+
+        if node.population == 0 {
+            return FALSE_LEAF;
+        }
+        if node.level == 0 {
+            return id;
+        }
+
+        let offset = if node.level == 1 {
+            0.0
+        } else {
+            Self::pow2(node.level - 2)
+        };
+
+        if x < 0.0 {
+            if y < 0.0 {
+                Self::node_get_cell(hashmap, node.nw, x + offset, y + offset)
+            } else {
+                Self::node_get_cell(hashmap, node.sw, x + offset, y - offset)
+            }
+        } else {
+            if y < 0.0 {
+                Self::node_get_cell(hashmap, node.ne, x - offset, y + offset)
+            } else {
+                Self::node_get_cell(hashmap, node.se, x - offset, y - offset)
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_bit(&mut self, x: f64, y: f64, living: bool) {
+        // log(format!("Setting bit at x: {}, y: {}, living: {}", x, y, living).as_str());
+        self.set_cell_state(x, y, living as u8);
+    }
+
+    #[allow(dead_code)]
+    pub fn get_bit(&self, x: f64, y: f64) -> bool {
+        self.get_cell_state(x, y) != 0
+    }
+
+    // like set_bit, for the states of a rule table
+    #[allow(dead_code)]
+    pub fn set_cell_state(&mut self, x: f64, y: f64, state: u8) {
+        let level = self.get_level_from_bounds(vec![x, y]);
+
+        if state != 0 {
+            while level > self.node(self.root()).level {
+                let root = self.expand_universe(self.root());
+                self.set_root(root);
+            }
+        } else if level > self.node(self.root()).level {
+            // no need to delete pixels outside of the universe
+            return;
+        }
+
+        let root = self.node_set_cell(self.root(), x, y, NodeId::leaf(state));
+        self.set_root(root);
+    }
+
+    #[allow(dead_code)]
+    pub fn get_cell_state(&self, x: f64, y: f64) -> u8 {
+        let level = self.get_level_from_bounds(vec![x, y]);
+        let root = self.root();
+        let hashmap = self.hashmap.borrow();
+
+        if level > hashmap[root].level {
+            return 0;
+        }
+
+        Self::node_get_cell(&hashmap, root, x, y).state()
+    }
+
+    fn node_get_boundary(
+        hashmap: &NodeMap,
+        node: NodeId,
+        left: f64,
+        top: f64,
+        find_mask: usize,
+        boundary: &mut Vec<f64>,
+    ) {
+        let node = &hashmap[node];
+
+        if node.population == 0 || find_mask == 0 {
+            return;
+        }
+
+        if node.level == 0 {
+            if left < boundary[0] {
+                boundary[0] = left;
+            }
+            if left > boundary[1] {
+                boundary[1] = left;
+            }
+
+            if top < boundary[2] {
+                boundary[2] = top;
+            }
+            if top > boundary[3] {
+                boundary[3] = top;
+            }
+        } else {
+            let offset = Self::pow2(node.level - 1);
+
+            if left >= boundary[0]
+                && left + offset * 2.0 <= boundary[1]
+                && top >= boundary[2]
+                && top + offset * 2.0 <= boundary[3]
             {
                 // this square is already inside the found boundary
                 return;
             }
 
-            let mut find_nw = find_mask;
-            let mut find_ne = find_mask;
-            let mut find_sw = find_mask;
-            let mut find_se = find_mask;
+            let mut find_nw = find_mask;
+            let mut find_ne = find_mask;
+            let mut find_sw = find_mask;
+            let mut find_se = find_mask;
+
+            if hashmap[node.nw].population != 0 {
+                find_sw &= !MASK_TOP;
+                find_ne &= !MASK_LEFT;
+                find_se &= !MASK_TOP & !MASK_LEFT;
+            }
+            if hashmap[node.sw].population != 0 {
+                find_se &= !MASK_LEFT;
+                find_nw &= !MASK_BOTTOM;
+                find_ne &= !MASK_BOTTOM & !MASK_LEFT;
+            }
+            if hashmap[node.ne].population != 0 {
+                find_nw &= !MASK_RIGHT;
+                find_se &= !MASK_TOP;
+                find_sw &= !MASK_TOP & !MASK_RIGHT;
+            }
+            if hashmap[node.se].population != 0 {
+                find_sw &= !MASK_RIGHT;
+                find_ne &= !MASK_BOTTOM;
+                find_nw &= !MASK_BOTTOM & !MASK_RIGHT;
+            }
+
+            Self::node_get_boundary(hashmap, node.nw, left, top, find_nw, boundary);
+            Self::node_get_boundary(hashmap, node.sw, left, top + offset, find_sw, boundary);
+            Self::node_get_boundary(hashmap, node.ne, left + offset, top, find_ne, boundary);
+            Self::node_get_boundary(
+                hashmap,
+                node.se,
+                left + offset,
+                top + offset,
+                find_se,
+                boundary,
+            );
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_root_bounds(&self) -> Vec<f64> {
+        let root_id = self.root();
+        let hashmap = self.hashmap.borrow();
+        let root = &hashmap[root_id];
+
+        if root.population == 0 {
+            return vec![0.0, 0.0, 0.0, 0.0];
+        }
+
+        let mut bounds = vec![
+            f64::INFINITY,     // left
+            f64::NEG_INFINITY, // right
+            f64::INFINITY,     // top
+            f64::NEG_INFINITY, // bottom
+        ];
+        let offset = Self::pow2(root.level - 1);
+
+        Self::node_get_boundary(
+            &hashmap,
+            root_id,
+            -offset,
+            -offset,
+            MASK_LEFT | MASK_TOP | MASK_RIGHT | MASK_BOTTOM,
+            &mut bounds,
+        );
+
+        bounds
+    }
+
+    fn expand_universe(&mut self, node: NodeId) -> NodeId {
+        let hashmap = &mut self.hashmap.borrow_mut();
+        let node = hashmap[node];
+        let t = hashmap.empty_tree(node.level - 1);
+        let nw = hashmap.create_tree(t, t, t, node.nw);
+        let ne = hashmap.create_tree(t, t, node.ne, t);
+        let sw = hashmap.create_tree(t, node.sw, t, t);
+        let se = hashmap.create_tree(node.se, t, t, t);
+
+        hashmap.create_tree(nw, ne, sw, se)
+    }
+
+    // the 4x4 cells of a level 2 node, row by row starting at the lowest bit
+    fn level2_bits(hashmap: &NodeMap, node: NodeId) -> u64 {
+        let level1 = |n: NodeId| {
+            let n = &hashmap[n];
+            let cell = |c: NodeId| hashmap[c].population as u64;
+            cell(n.nw) | cell(n.ne) << 1 | cell(n.sw) << 4 | cell(n.se) << 5
+        };
+        let node = &hashmap[node];
+
+        level1(node.nw) | level1(node.ne) << 2 | level1(node.sw) << 8 | level1(node.se) << 10
+    }
+
+    // the 8x8 cells of a level 3 node as a bitboard, row by row starting at the
+    // lowest bit. With dead and living cells only that is just its children.
+    fn level3_bits(hashmap: &NodeMap, node: NodeId) -> u64 {
+        let bits = |child: NodeId| {
+            spread_rows(
+                child
+                    .level2_bits()
+                    .unwrap_or_else(|| Self::level2_bits(hashmap, child)),
+            )
+        };
+        let node = &hashmap[node];
+
+        bits(node.nw) | bits(node.ne) << 4 | bits(node.sw) << 32 | bits(node.se) << 36
+    }
+
+    // bitboard_next with the rules of this universe
+    fn bitboard_next(&self, board: u64) -> u64 {
+        bitboard_next(board, self.rule_b, self.rule_s)
+    }
+
+    // the level 2 node of the 4x4 cells of a bitboard starting at row and column
+    fn level2_from_bits(board: u64, row: usize, col: usize) -> NodeId {
+        let rows = (0..4).map(|r| (board >> ((row + r) * 8 + col) & 0xF) << (r * 4));
+        NodeId::level2(rows.fold(0, |bits, row| bits | row))
+    }
+
+    // steps the 8x8 cells of a level 3 node by one or, if quick, two
+    // generations at once and returns the 4x4 centre
+    fn node_level3_next(&mut self, node: NodeId, quick: bool) -> NodeId {
+        let mut board = self.bitboard_next(Self::level3_bits(&self.hashmap.borrow(), node));
+        if quick {
+            board = self.bitboard_next(board);
+        }
+
+        Self::level2_from_bits(board, 2, 2)
+    }
+
+    fn node_level2_next(&mut self, node: NodeId) -> NodeId {
+        let bitmask = {
+            let hashmap = self.hashmap.borrow();
+            let [nw, ne, sw, se] = hashmap[node].children().map(|n| hashmap[n]);
+            let cell = |c: NodeId| hashmap[c].population;
+
+            cell(nw.nw) << 15
+                | cell(nw.ne) << 14
+                | cell(ne.nw) << 13
+                | cell(ne.ne) << 12
+                | cell(nw.sw) << 11
+                | cell(nw.se) << 10
+                | cell(ne.sw) << 9
+                | cell(ne.se) << 8
+                | cell(sw.nw) << 7
+                | cell(sw.ne) << 6
+                | cell(se.nw) << 5
+                | cell(se.ne) << 4
+                | cell(sw.sw) << 3
+                | cell(sw.se) << 2
+                | cell(se.sw) << 1
+                | cell(se.se)
+        };
+
+        self.level1_create(
+            self.eval_mask(bitmask >> 5)
+                | self.eval_mask(bitmask >> 4) << 1
+                | self.eval_mask(bitmask >> 1) << 2
+                | self.eval_mask(bitmask) << 3,
+        )
+    }
+
+    // like node_level2_next, for the states of a rule table
+    fn node_table_next(&mut self, node: NodeId, table: &RuleTable) -> NodeId {
+        let cells =
+            Self::grandchildren(&self.hashmap.borrow(), node).map(|row| row.map(NodeId::state));
+
+        let next = |row: usize, col: usize| {
+            NodeId::leaf(table.next([
+                cells[row][col],
+                cells[row - 1][col],
+                cells[row - 1][col + 1],
+                cells[row][col + 1],
+                cells[row + 1][col + 1],
+                cells[row + 1][col],
+                cells[row + 1][col - 1],
+                cells[row][col - 1],
+                cells[row - 1][col - 1],
+            ]))
+        };
+
+        self.create_tree(next(1, 1), next(1, 2), next(2, 1), next(2, 2))
+    }
+
+    // the states of the cells of a node, row by row into a grid of the given width
+    fn node_states(
+        hashmap: &NodeMap,
+        node: NodeId,
+        x: usize,
+        y: usize,
+        width: usize,
+        states: &mut [u8],
+    ) {
+        let tree_node = &hashmap[node];
+
+        if tree_node.population == 0 {
+            return;
+        }
+        if tree_node.level == 0 {
+            states[y * width + x] = node.state();
+            return;
+        }
+
+        let half = 1 << (tree_node.level - 1);
+        Self::node_states(hashmap, tree_node.nw, x, y, width, states);
+        Self::node_states(hashmap, tree_node.ne, x + half, y, width, states);
+        Self::node_states(hashmap, tree_node.sw, x, y + half, width, states);
+        Self::node_states(hashmap, tree_node.se, x + half, y + half, width, states);
+    }
+
+    // the node of a square of a grid of states with the given width
+    fn node_from_states(
+        &mut self,
+        states: &[u8],
+        x: usize,
+        y: usize,
+        width: usize,
+        level: usize,
+    ) -> NodeId {
+        if level == 0 {
+            return NodeId::leaf(states[y * width + x]);
+        }
+
+        let half = 1 << (level - 1);
+        let nw = self.node_from_states(states, x, y, width, level - 1);
+        let ne = self.node_from_states(states, x + half, y, width, level - 1);
+        let sw = self.node_from_states(states, x, y + half, width, level - 1);
+        let se = self.node_from_states(states, x + half, y + half, width, level - 1);
+        self.create_tree(nw, ne, sw, se)
+    }
+
+    // the centre of a node at the base level of a larger than life rule, one
+    // generation later
+    fn node_ltl_next(&mut self, node: NodeId, rule: &LargerThanLife) -> NodeId {
+        let level = self.node(node).level;
+        let size = 1 << level;
+        let mut scratch = mem::take(&mut self.ltl_scratch);
+        scratch.cells.clear();
+        scratch.cells.resize(size * size, 0);
+        Self::node_states(&self.hashmap.borrow(), node, 0, 0, size, &mut scratch.cells);
+
+        rule.next_block(&mut scratch, size);
+        let next = self.node_from_states(&scratch.next, 0, 0, size / 2, level - 1);
+        self.ltl_scratch = scratch;
+        next
+    }
+
+    #[allow(dead_code)]
+    fn node_quick_next_generation(&mut self, node: NodeId) -> NodeId {
+        self.node_step(node, true)
+    }
+
+    fn node_next_generation(&mut self, node: NodeId) -> NodeId {
+        self.node_step(node, false)
+    }
+
+    fn node_step(&mut self, node: NodeId, quick: bool) -> NodeId {
+        let quick = quick || self.step == self.node(node).level - self.base_level();
+
+        if let Some(result) = self.try_step(node, quick) {
+            return result;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(result) = parallel::node_step(self, node, quick) {
+            return result;
+        }
+
+        let mut stack = vec![StepFrame::new(node, quick)];
+        self.run_step(&mut stack, f64::INFINITY)
+            .expect("step without a deadline is finished")
+    }
+
+    // works through the stack until the result is known or the deadline (in ms) has passed
+    fn run_step(&mut self, stack: &mut Vec<StepFrame>, deadline: f64) -> Option<NodeId> {
+        let mut count = 0;
+
+        while let Some(frame) = stack.last_mut() {
+            match self.step_frame(frame) {
+                StepAction::Push(child, quick) => {
+                    let quick = quick || self.step == self.node(child).level - self.base_level();
+
+                    // cached results and level 2 nodes don't need a frame of their own
+                    if let Some(result) = self.try_step(child, quick) {
+                        frame.push(result);
+                    } else {
+                        stack.push(StepFrame::new(child, quick));
+                    }
+                }
+                StepAction::Return(result) => {
+                    stack.pop();
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(result),
+                        None => return Some(result),
+                    }
+                }
+            }
+
+            // between frames every node in use is on the stack, so this is
+            // where memory is reclaimed
+            let mut hashmap = self.hashmap.borrow_mut();
+            if hashmap.over_limit() && !hashmap.out_of_memory {
+                let roots: Vec<NodeId> = stack.iter().flat_map(StepFrame::nodes).collect();
+                hashmap.garbage_collect(&roots);
+            }
+            drop(hashmap);
+
+            count += 1;
+            if count % 1024 == 0 && now_ms() > deadline {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn try_step(&mut self, node: NodeId, quick: bool) -> Option<NodeId> {
+        let TreeNode {
+            nw,
+            level,
+            cache,
+            quick_cache,
+            ..
+        } = self.node(node);
+        let cached = if quick { quick_cache } else { cache };
+
+        if let Some(cached) = cached {
+            debug_assert_eq!(self.node(cached).level, level - 1);
+            self.hashmap.borrow_mut().stats.cache_hits += 1;
+            return Some(cached);
+        }
+
+        self.hashmap.borrow_mut().stats.cache_misses += 1;
+
+        if self.hashmap.borrow().out_of_memory {
+            // the step is abandoned, any node of the right level will do
+            return Some(nw);
+        }
+
+        if level == 3 && self.custom_rule.is_none() {
+            let new_node = self.node_level3_next(node, quick);
+            let mut hashmap = self.hashmap.borrow_mut();
+            if quick {
+                hashmap[node].quick_cache = Some(new_node);
+            } else {
+                hashmap[node].cache = Some(new_node);
+            }
+            return Some(new_node);
+        }
+
+        if level == self.base_level() {
+            let new_node = match self.custom_rule.clone() {
+                Some(CustomRule::Table(table)) => self.node_table_next(node, &table),
+                Some(CustomRule::LargerThanLife(rule)) => self.node_ltl_next(node, &rule),
+                None => self.node_level2_next(node),
+            };
+            self.hashmap.borrow_mut()[node].quick_cache = Some(new_node);
+            return Some(new_node);
+        }
+
+        None
+    }
+
+    // the grandchildren of a node as a 4x4 grid, by row and column
+    fn grandchildren(hashmap: &NodeMap, node: NodeId) -> [[NodeId; 4]; 4] {
+        let [nw, ne, sw, se] = hashmap[node].children().map(|n| hashmap[n]);
+
+        [
+            [nw.nw, nw.ne, ne.nw, ne.ne],
+            [nw.sw, nw.se, ne.sw, ne.se],
+            [sw.nw, sw.ne, se.nw, se.ne],
+            [sw.sw, sw.se, se.sw, se.se],
+        ]
+    }
+
+    fn step_frame(&mut self, frame: &mut StepFrame) -> StepAction {
+        let hashmap = &mut self.hashmap.borrow_mut();
+
+        if frame.len == 0 && !frame.quick {
+            // not advancing at this level, the nine subnodes are just centred
+            let g = Self::grandchildren(hashmap, frame.node);
+
+            for i in 0..9 {
+                let (row, col) = (i / 3, i % 3);
+                let children = [
+                    hashmap[g[row][col]].se,
+                    hashmap[g[row][col + 1]].sw,
+                    hashmap[g[row + 1][col]].ne,
+                    hashmap[g[row + 1][col + 1]].nw,
+                ];
+                let [nw, ne, sw, se] = children;
+                frame.push(hashmap.create_tree(nw, ne, sw, se));
+            }
+        }
+
+        if frame.len < 9 {
+            // advancing at this level, the nine overlapping subnodes are stepped first
+            let node = hashmap[frame.node];
+
+            let child = match frame.len {
+                0 => node.nw,
+                2 => node.ne,
+                6 => node.sw,
+                8 => node.se,
+                i => {
+                    let g = Self::grandchildren(hashmap, frame.node);
+                    let (row, col) = (i / 3, i % 3);
+                    hashmap.create_tree(
+                        g[row][col],
+                        g[row][col + 1],
+                        g[row + 1][col],
+                        g[row + 1][col + 1],
+                    )
+                }
+            };
+            return StepAction::Push(child, true);
+        }
+
+        if frame.len < 13 {
+            // n00_n01_n10_n11, n01_n02_n11_n12, n10_n11_n20_n21, n11_n12_n21_n22
+            let [nw, ne, sw, se] = match frame.len {
+                9 => [0, 1, 3, 4],
+                10 => [1, 2, 4, 5],
+                11 => [3, 4, 6, 7],
+                _ => [4, 5, 7, 8],
+            };
+            let tree = hashmap.create_tree(
+                frame.part(nw),
+                frame.part(ne),
+                frame.part(sw),
+                frame.part(se),
+            );
+            return StepAction::Push(tree, frame.quick);
+        }
+
+        let new_node = hashmap.create_tree(
+            frame.part(9),
+            frame.part(10),
+            frame.part(11),
+            frame.part(12),
+        );
+
+        debug_assert_eq!(hashmap[new_node].level, hashmap[frame.node].level - 1);
+        if !hashmap.out_of_memory {
+            if frame.quick {
+                hashmap[frame.node].quick_cache = Some(new_node);
+            } else {
+                hashmap[frame.node].cache = Some(new_node);
+            }
+        }
+        StepAction::Return(new_node)
+    }
+
+    #[allow(dead_code)]
+    pub fn next_generation(&mut self, is_single: bool) -> bool {
+        if self.get_active_engine() == Engine::QuickLife
+            && let Some(done) = self.quicklife_generation()
+        {
+            return done;
+        }
+
+        /*unsafe {
+            COLLISION_COUNT = 0;
+        }*/
+        let misses = self.hashmap.borrow().stats.cache_misses;
+        let root = self.expanded_root(is_single);
+
+        // superstep button doesn't exist
+        /*if is_single {
+            self.generation += Self::pow2(self.step);
+            root = self.node_next_generation(root);
+        } else {
+            self.generation += Self::pow2(self.root.level - 2);
+            root = self.node_quick_next_generation(root);
+        }*/
+        let root = self.node_next_generation(root);
+
+        // log(format!("Collision count: {}", unsafe { COLLISION_COUNT }).as_str());
+
+        let done = self.finish_step(root);
+        self.judge_hashlife(misses);
+        done
+    }
+
+    #[allow(dead_code)]
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.poor_steps = 0;
+        self.quicklife_until = 0.0;
+
+        if engine == Engine::Hashlife {
+            // the root is up to date once the tiles are gone
+            let root = self.root();
+            self.set_root(root);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_engine(&self) -> Engine {
+        self.engine
+    }
 
-            if node.nw.population != 0 {
-                find_sw &= !MASK_TOP;
-                find_ne &= !MASK_LEFT;
-                find_se &= !MASK_TOP & !MASK_LEFT;
+    // the engine that computes the next generation, never auto
+    #[allow(dead_code)]
+    pub fn get_active_engine(&self) -> Engine {
+        match self.engine {
+            // quicklife only knows two states
+            _ if self.custom_rule.is_some() => Engine::Hashlife,
+            // with births on no neighbours every tile of the plane comes alive
+            _ if self.rule_b & 1 != 0 => Engine::Hashlife,
+            Engine::Auto if self.generation < self.quicklife_until => Engine::QuickLife,
+            Engine::Auto => Engine::Hashlife,
+            engine => engine,
+        }
+    }
+
+    // lets the auto engine switch to quicklife if the last step, which
+    // started at the given cache misses, found few results in the cache
+    fn judge_hashlife(&mut self, misses: usize) {
+        if self.engine != Engine::Auto {
+            return;
+        }
+
+        let misses = self.hashmap.borrow().stats.cache_misses - misses;
+        let per_generation = misses as f64 / Self::pow2(self.step);
+
+        if misses >= AUTO_MIN_MISSES
+            && per_generation * AUTO_CELLS_PER_MISS > self.node(self.root()).population as f64
+        {
+            self.poor_steps += 1;
+        } else {
+            self.poor_steps = 0;
+        }
+
+        if self.poor_steps >= AUTO_POOR_STEPS {
+            self.poor_steps = 0;
+            self.quicklife_until = self.generation + AUTO_QUICKLIFE_GENERATIONS;
+        }
+    }
+
+    // steps the pattern one generation at a time on quicklife tiles, which
+    // stay ahead of the root until it is needed. None if the pattern or the
+    // step is too large for them
+    fn quicklife_generation(&mut self) -> Option<bool> {
+        if self.step > QUICKLIFE_MAX_STEP {
+            return None;
+        }
+
+        let mut quicklife = match self.tiles.take() {
+            Some(quicklife) => quicklife,
+            None => self.tiles_from_root()?,
+        };
+
+        // cells move by at most one tile every 64 generations
+        let generations = 1u64 << self.step;
+        let extent = quicklife.extent() + (generations >> quicklife::TILE_BITS) as i64 + 1;
+        if Self::tiles_level(extent) > QUICKLIFE_MAX_LEVEL {
+            self.tiles = Some(quicklife);
+            return None;
+        }
+
+        for _ in 0..generations {
+            quicklife.step(self.rule_b, self.rule_s);
+        }
+        self.tiles = Some(quicklife);
+        self.root_behind.set(true);
+        self.generation += Self::pow2(self.step);
+
+        if self.cell_history.is_some() {
+            let previous = self.root.get();
+            self.root();
+            self.record_cell_history(previous);
+        }
+
+        let mut hashmap = self.hashmap.borrow_mut();
+        if hashmap.wants_collection() {
+            hashmap.garbage_collect(&[]);
+        }
+        Some(true)
+    }
+
+    fn tiles_from_root(&mut self) -> Option<quicklife::QuickLife> {
+        // level 6 nodes line up with the tiles once the root is larger than them
+        let root = self.expand_to_level(self.root(), quicklife::TILE_BITS + 1);
+        let hashmap = self.hashmap.borrow();
+        let level = hashmap[root].level;
+        if level > QUICKLIFE_MAX_LEVEL {
+            return None;
+        }
+
+        let mut quicklife = quicklife::QuickLife::new();
+        let half = 1i64 << (level - 1);
+        let mut stack = vec![(root, -half, -half)];
+
+        while let Some((id, left, top)) = stack.pop() {
+            let node = &hashmap[id];
+            if node.population == 0 {
+                continue;
             }
-            if node.sw.population != 0 {
-                find_se &= !MASK_LEFT;
-                find_nw &= !MASK_BOTTOM;
-                find_ne &= !MASK_BOTTOM & !MASK_LEFT;
+
+            if node.level == quicklife::TILE_BITS {
+                let key = (left >> quicklife::TILE_BITS, top >> quicklife::TILE_BITS);
+                let tile = quicklife
+                    .tiles
+                    .entry(key)
+                    .or_insert_with(|| Box::new([0; 64]));
+                Self::fill_tile(&hashmap, id, 0, 0, tile);
+                continue;
             }
-            if node.ne.population != 0 {
-                find_nw &= !MASK_RIGHT;
-                find_se &= !MASK_TOP;
-                find_sw &= !MASK_TOP & !MASK_RIGHT;
+
+            let half = 1i64 << (node.level - 1);
+            stack.push((node.nw, left, top));
+            stack.push((node.ne, left + half, top));
+            stack.push((node.sw, left, top + half));
+            stack.push((node.se, left + half, top + half));
+        }
+
+        Some(quicklife)
+    }
+
+    // copies the cells of a node into the tile at column x and row y
+    fn fill_tile(hashmap: &NodeMap, id: NodeId, x: usize, y: usize, tile: &mut quicklife::Tile) {
+        let node = &hashmap[id];
+        if node.population == 0 {
+            return;
+        }
+
+        if node.level == 3 {
+            let bits = Self::level3_bits(hashmap, id);
+            for i in 0..8 {
+                tile[y + i] |= (bits >> (8 * i) & 0xFF) << x;
             }
-            if node.se.population != 0 {
-                find_sw &= !MASK_RIGHT;
-                find_ne &= !MASK_BOTTOM;
-                find_nw &= !MASK_BOTTOM & !MASK_RIGHT;
+            return;
+        }
+
+        let half = 1 << (node.level - 1);
+        Self::fill_tile(hashmap, node.nw, x, y, tile);
+        Self::fill_tile(hashmap, node.ne, x + half, y, tile);
+        Self::fill_tile(hashmap, node.sw, x, y + half, tile);
+        Self::fill_tile(hashmap, node.se, x + half, y + half, tile);
+    }
+
+    // the level of the smallest root centred on the origin that holds the
+    // tiles up to the given extent
+    fn tiles_level(extent: i64) -> usize {
+        let mut level = quicklife::TILE_BITS + 1;
+        while 1i64 << (level - 1 - quicklife::TILE_BITS) <= extent {
+            level += 1;
+        }
+        level
+    }
+
+    fn root_from_tiles(&self, quicklife: &quicklife::QuickLife) -> Option<NodeId> {
+        let mut tiles: Vec<_> = quicklife
+            .tiles
+            .iter()
+            .map(|(&(x, y), tile)| (x, y, &**tile))
+            .collect();
+
+        let level = Self::tiles_level(quicklife.extent());
+        if level > QUICKLIFE_MAX_LEVEL {
+            return None;
+        }
+
+        let half = 1i64 << (level - 1 - quicklife::TILE_BITS);
+        Some(self.node_from_tiles(&mut tiles, level, -half, -half))
+    }
+
+    // the node of the given level with its top left tile at left and top,
+    // tiles holds every tile inside it
+    fn node_from_tiles(
+        &self,
+        tiles: &mut [(i64, i64, &quicklife::Tile)],
+        level: usize,
+        left: i64,
+        top: i64,
+    ) -> NodeId {
+        if tiles.is_empty() {
+            return self.empty_tree(level);
+        }
+
+        if level == quicklife::TILE_BITS {
+            return self.tile_node(tiles[0].2, level, 0, 0);
+        }
+
+        let half = 1i64 << (level - 1 - quicklife::TILE_BITS);
+        let quadrant = |&(x, y, _): &(i64, i64, &quicklife::Tile)| {
+            (y >= top + half) as usize * 2 + (x >= left + half) as usize
+        };
+        tiles.sort_unstable_by_key(quadrant);
+
+        let (nw, rest) = tiles.split_at_mut(tiles.partition_point(|t| quadrant(t) < 1));
+        let (ne, rest) = rest.split_at_mut(rest.partition_point(|t| quadrant(t) < 2));
+        let (sw, se) = rest.split_at_mut(rest.partition_point(|t| quadrant(t) < 3));
+
+        let nw = self.node_from_tiles(nw, level - 1, left, top);
+        let ne = self.node_from_tiles(ne, level - 1, left + half, top);
+        let sw = self.node_from_tiles(sw, level - 1, left, top + half);
+        let se = self.node_from_tiles(se, level - 1, left + half, top + half);
+
+        self.create_tree(nw, ne, sw, se)
+    }
+
+    // the node of the cells of a tile starting at column x and row y
+    fn tile_node(&self, tile: &quicklife::Tile, level: usize, x: usize, y: usize) -> NodeId {
+        if level == 3 {
+            let board = (0..8).fold(0, |board, i| board | (tile[y + i] >> x & 0xFF) << (8 * i));
+            if board == 0 {
+                return self.empty_tree(3);
+            }
+
+            let nw = Self::level2_from_bits(board, 0, 0);
+            let ne = Self::level2_from_bits(board, 0, 4);
+            let sw = Self::level2_from_bits(board, 4, 0);
+            let se = Self::level2_from_bits(board, 4, 4);
+
+            return self.create_tree(nw, ne, sw, se);
+        }
+
+        let half = 1 << (level - 1);
+        let nw = self.tile_node(tile, level - 1, x, y);
+        let ne = self.tile_node(tile, level - 1, x + half, y);
+        let sw = self.tile_node(tile, level - 1, x, y + half);
+        let se = self.tile_node(tile, level - 1, x + half, y + half);
+
+        self.create_tree(nw, ne, sw, se)
+    }
+
+    // the root, expanded until the next generation fits into its centre
+    fn expanded_root(&mut self, is_single: bool) -> NodeId {
+        self.use_caches();
+        self.hashmap.borrow_mut().out_of_memory = false;
+        let mut root = self.root();
+
+        loop {
+            let hashmap = self.hashmap.borrow();
+            let [nw, ne, sw, se] = hashmap[root].children().map(|n| hashmap[n]);
+            let inner = |n: NodeId, corner: fn(&TreeNode) -> NodeId| {
+                hashmap[corner(&hashmap[n])].population
+            };
+
+            let base = self.base_level();
+            let fits = !(is_single && hashmap[root].level <= self.step + base
+                || hashmap[root].level <= base)
+                && nw.population == inner(nw.se, |n| n.se)
+                && ne.population == inner(ne.sw, |n| n.sw)
+                && sw.population == inner(sw.ne, |n| n.ne)
+                && se.population == inner(se.nw, |n| n.nw);
+            drop(hashmap);
+
+            if fits {
+                return root;
             }
+            root = self.expand_universe(root);
+        }
+    }
+
+    fn finish_step(&mut self, root: NodeId) -> bool {
+        if self.hashmap.borrow().out_of_memory {
+            // memory limit reached, keep the current generation
+            return false;
+        }
+
+        self.generation += Self::pow2(self.step);
+        let previous = self.root();
+        self.set_root(root);
+        self.record_cell_history(previous);
 
-            self.node_get_boundary(&node.nw, left, top, find_nw, boundary);
-            self.node_get_boundary(&node.sw, left, top + offset, find_sw, boundary);
-            self.node_get_boundary(&node.ne, left + offset, top, find_ne, boundary);
-            self.node_get_boundary(&node.se, left + offset, top + offset, find_se, boundary);
+        let mut hashmap = self.hashmap.borrow_mut();
+        if hashmap.wants_collection() {
+            hashmap.garbage_collect(&[]);
         }
+        true
     }
 
+    // number of threads used by next_generation, 1 steps on the calling thread
+    #[cfg(not(target_arch = "wasm32"))]
     #[allow(dead_code)]
-    pub fn get_root_bounds(&self) -> Vec<f64> {
-        if self.root.population == 0 {
-            return vec![0.0, 0.0, 0.0, 0.0];
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // starts a step that is computed by calls to continue_step, so that the
+    // caller can show progress in between or cancel it
+    #[allow(dead_code)]
+    pub fn begin_step(&mut self, is_single: bool) {
+        let from = self.root();
+        let collections = self.hashmap.borrow().collections;
+        let misses = self.hashmap.borrow().stats.cache_misses;
+        let mut pending = PendingStep {
+            from,
+            is_single,
+            stack: vec![],
+            result: None,
+            collections,
+            misses,
+            quicklife: self.get_active_engine() == Engine::QuickLife,
+        };
+
+        if !pending.quicklife {
+            self.push_root_frame(&mut pending);
         }
+        self.pending_step = Some(pending);
+    }
 
-        let mut bounds = vec![
-            f64::INFINITY,     // left
-            f64::NEG_INFINITY, // right
-            f64::INFINITY,     // top
-            f64::NEG_INFINITY, // bottom
-        ];
-        let offset = Self::pow2(self.root.level - 1);
+    // the first frame of a hashlife step, or its result if it is cached
+    fn push_root_frame(&mut self, pending: &mut PendingStep) {
+        let root = self.expanded_root(pending.is_single);
+        let quick = self.step == self.node(root).level - self.base_level();
 
-        self.node_get_boundary(
-            &self.root,
-            -offset,
-            -offset,
-            MASK_LEFT | MASK_TOP | MASK_RIGHT | MASK_BOTTOM,
-            &mut bounds,
-        );
+        match self.try_step(root, quick) {
+            Some(result) => pending.result = Some(result),
+            None => pending.stack.push(StepFrame::new(root, quick)),
+        }
+    }
 
-        bounds
+    // works on the pending step for about budget_ms. Like next_generation, a
+    // step that runs out of memory is dropped.
+    #[allow(dead_code)]
+    pub fn continue_step(&mut self, budget_ms: f64) -> StepStatus {
+        let Some(mut pending) = self.pending_step.take() else {
+            return StepStatus::Idle;
+        };
+
+        if pending.from != self.root() {
+            return StepStatus::Edited;
+        }
+
+        if pending.quicklife {
+            match self.quicklife_generation() {
+                Some(true) => return StepStatus::Done,
+                Some(false) => return StepStatus::OutOfMemory,
+                None => {
+                    // too large for the tiles, hashlife does this step
+                    pending.quicklife = false;
+                    self.push_root_frame(&mut pending);
+                }
+            }
+        }
+
+        let cached_for = Some(self.cache_key());
+        let hashmap = self.hashmap.borrow();
+        let stale = hashmap.cached_for != cached_for || hashmap.collections != pending.collections;
+        drop(hashmap);
+
+        if stale {
+            // a universe sharing the store flushed the cached results or
+            // collected the nodes of the stack, start over
+            self.begin_step(pending.is_single);
+            return self.continue_step(budget_ms);
+        }
+
+        if pending.result.is_none() {
+            pending.result = self.run_step(&mut pending.stack, now_ms() + budget_ms);
+            pending.collections = self.hashmap.borrow().collections;
+        }
+
+        let Some(root) = pending.result.take() else {
+            self.pending_step = Some(pending);
+            return StepStatus::Working;
+        };
+
+        let done = self.finish_step(root);
+        self.judge_hashlife(pending.misses);
+        if done {
+            StepStatus::Done
+        } else {
+            StepStatus::OutOfMemory
+        }
     }
 
-    fn expand_universe(&mut self, node: Rc<TreeNode>) -> Rc<TreeNode> {
-        let level = node.level;
-        let hashmap = &mut self.hashmap;
-        let t = Self::empty_tree(&mut self.empty_tree_cache, &self.false_leaf, hashmap, &self.root, level - 1);
-        let nw = Self::create_tree(hashmap, &self.root, &t, &t, &t, &node.nw);
-        let ne = Self::create_tree(hashmap, &self.root, &t, &t, &node.ne, &t);
-        let sw = Self::create_tree(hashmap, &self.root, &t, &node.sw, &t, &t);
-        let se = Self::create_tree(hashmap, &self.root, &node.se, &t, &t, &t);
+    #[allow(dead_code)]
+    pub fn cancel_step(&mut self) {
+        self.pending_step = None;
+    }
 
-        Self::create_tree(&mut self.hashmap, &self.root, &nw, &ne, &sw, &se)
+    #[allow(dead_code)]
+    pub fn has_pending_step(&self) -> bool {
+        self.pending_step.is_some()
     }
 
-    fn uncache(&mut self, also_quick: bool) {
-        for (_, n) in &mut self.hashmap {
-            n.cache.take();
-            if also_quick {
-                n.quick_cache.take();
-            }
-        }
-    }
-
-    fn node_level2_next(&mut self, node: &Rc<TreeNode>) -> Rc<TreeNode> {
-        let nw = &node.nw;
-        let ne = &node.ne;
-        let sw = &node.sw;
-        let se = &node.se;
-        let bitmask = nw.nw.population << 15
-            | nw.ne.population << 14
-            | ne.nw.population << 13
-            | ne.ne.population << 12
-            | nw.sw.population << 11
-            | nw.se.population << 10
-            | ne.sw.population << 9
-            | ne.se.population << 8
-            | sw.nw.population << 7
-            | sw.ne.population << 6
-            | se.nw.population << 5
-            | se.ne.population << 4
-            | sw.sw.population << 3
-            | sw.se.population << 2
-            | se.sw.population << 1
-            | se.se.population;
+    // estimated fraction of the pending step that is done
+    #[allow(dead_code)]
+    pub fn get_step_progress(&self) -> f64 {
+        let Some(pending) = &self.pending_step else {
+            return 1.0;
+        };
+        if pending.result.is_some() {
+            return 1.0;
+        }
 
-        self.level1_create(
-            self.eval_mask(bitmask >> 5)
-                | self.eval_mask(bitmask >> 4) << 1
-                | self.eval_mask(bitmask >> 1) << 2
-                | self.eval_mask(bitmask) << 3,
-        )
+        // every frame is one of the 13 parts of its parent
+        let mut progress = 0.0;
+        let mut scale = 1.0;
+
+        for frame in &pending.stack {
+            progress += scale * frame.len as f64 / 13.0;
+            scale /= 13.0;
+        }
+
+        progress
     }
 
-    fn node_quick_next_generation(&mut self, node: &Rc<TreeNode>) -> Rc<TreeNode> {
-        if let Some(cached) = node.get_quick_cache() {
-            debug_assert_eq!(cached.level, node.level - 1);
-            return cached;
+    fn expand_to_level(&mut self, mut node: NodeId, level: usize) -> NodeId {
+        while self.node(node).level < level {
+            node = self.expand_universe(node);
         }
+        node
+    }
 
-        if node.level == 2 {
-            let new_node = self.node_level2_next(&node);
-            node.quick_cache.set(Some(new_node.clone()));
-            return new_node;
+    // both nodes have to be of the same level and from this universe
+    fn node_boolean(&mut self, op: BooleanOp, a: NodeId, b: NodeId) -> NodeId {
+        if let Some(result) = self.known_boolean(op, a, b) {
+            return result;
         }
 
-        let nw = &node.nw;
-        let ne = &node.ne;
-        let sw = &node.sw;
-        let se = &node.se;
-        let n00 = self.node_quick_next_generation(&nw);
-        let n01_tree = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.ne,
-            &ne.nw,
-            &nw.se,
-            &ne.sw,
-        );
-        let n01 = self.node_quick_next_generation(&n01_tree);
-        let n02 = self.node_quick_next_generation(&ne);
-        let n10_tree = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.sw,
-            &nw.se,
-            &sw.nw,
-            &sw.ne,
-        );
-        let n10 = self.node_quick_next_generation(&n10_tree);
-        let n11_tree = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.se,
-            &ne.sw,
-            &sw.ne,
-            &se.nw,
-        );
-        let n11 = self.node_quick_next_generation(&n11_tree);
-        let n12_tree = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &ne.sw,
-            &ne.se,
-            &se.nw,
-            &se.ne,
-        );
-        let n12 = self.node_quick_next_generation(&n12_tree);
-        let n20 = self.node_quick_next_generation(&sw);
-        let n21_tree = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &sw.ne,
-            &se.nw,
-            &sw.se,
-            &se.sw,
-        );
-        let n21 = self.node_quick_next_generation(&n21_tree);
-        let n22 = self.node_quick_next_generation(&se);
-
-        let n00_n01_n10_n11 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n00, &n01, &n10, &n11);
-        let n01_n02_n11_n12 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n01, &n02, &n11, &n12);
-        let n10_n11_n20_n21 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n10, &n11, &n20, &n21);
-        let n11_n12_n21_n22 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n11, &n12, &n21, &n22);
-
-        let nw_tree = self.node_quick_next_generation(&n00_n01_n10_n11);
-        let ne_tree = self.node_quick_next_generation(&n01_n02_n11_n12);
-        let sw_tree = self.node_quick_next_generation(&n10_n11_n20_n21);
-        let se_tree = self.node_quick_next_generation(&n11_n12_n21_n22);
-
-        let new_node = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw_tree,
-            &ne_tree,
-            &sw_tree,
-            &se_tree,
-        );
+        // pairs of nodes whose quadrants are being combined, with the results
+        // of the quadrants so far, kept on an explicit stack like StepFrame
+        let mut stack = vec![(a, b, [FALSE_LEAF; 4], 0)];
+
+        loop {
+            let (a, b, results, len) = stack.last_mut().expect("the first pair is popped last");
+
+            if *len < 4 {
+                let (child_a, child_b) = (
+                    self.node(*a).children()[*len],
+                    self.node(*b).children()[*len],
+                );
+                match self.known_boolean(op, child_a, child_b) {
+                    Some(result) => {
+                        results[*len] = result;
+                        *len += 1;
+                    }
+                    None => stack.push((child_a, child_b, [FALSE_LEAF; 4], 0)),
+                }
+                continue;
+            }
 
-        debug_assert_eq!(new_node.level, node.level - 1);
-        node.quick_cache.set(Some(new_node.clone()));
-        new_node
+            let [nw, ne, sw, se] = *results;
+            let result = self.create_tree(nw, ne, sw, se);
+            self.hashmap
+                .borrow_mut()
+                .booleans
+                .insert([op as u32, a.0.get(), b.0.get()], result);
+            stack.pop();
+
+            let Some((_, _, results, len)) = stack.last_mut() else {
+                return result;
+            };
+            results[*len] = result;
+            *len += 1;
+        }
     }
 
-    fn node_next_generation(&mut self, node: &Rc<TreeNode>) -> Rc<TreeNode> {
-        if let Some(cached) = node.get_cache() {
-            return cached;
+    // the result of node_boolean if it doesn't need the quadrants of both
+    // nodes: for leaves, empty or equal nodes and results found before
+    fn known_boolean(&mut self, op: BooleanOp, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let same = a == b;
+        let (node_a, node_b) = (self.node(a), self.node(b));
+
+        let shortcut = match op {
+            BooleanOp::Union if same || node_b.population == 0 => Some(a),
+            BooleanOp::Union if node_a.population == 0 => Some(b),
+            BooleanOp::Intersect if same || node_a.population == 0 => Some(a),
+            BooleanOp::Intersect if node_b.population == 0 => Some(b),
+            BooleanOp::Difference if node_a.population == 0 || node_b.population == 0 => Some(a),
+            BooleanOp::Xor if node_a.population == 0 => Some(b),
+            BooleanOp::Xor if node_b.population == 0 => Some(a),
+            BooleanOp::Difference | BooleanOp::Xor if same => Some(self.empty_tree(node_a.level)),
+            _ => None,
+        };
+
+        if shortcut.is_some() {
+            return shortcut;
         }
 
-        if self.step == node.level - 2 {
-            return self.node_quick_next_generation(&node);
+        if node_a.level == 0 {
+            let alive = match op {
+                BooleanOp::Union => node_a.population | node_b.population,
+                BooleanOp::Intersect => node_a.population & node_b.population,
+                BooleanOp::Difference => node_a.population & !node_b.population,
+                BooleanOp::Xor => node_a.population ^ node_b.population,
+            };
+
+            return Some(if alive & 1 != 0 {
+                TRUE_LEAF
+            } else {
+                FALSE_LEAF
+            });
         }
 
-        if node.level == 2 {
-            if let Some(cached) = node.get_quick_cache() {
-                return cached;
+        self.hashmap
+            .borrow()
+            .booleans
+            .get(&[op as u32, a.0.get(), b.0.get()])
+            .copied()
+    }
+
+    // copies a node of another store into this one, so that it can be used
+    // with the nodes here without sharing their cached results
+    fn import_node(
+        &mut self,
+        other: &NodeMap,
+        node: NodeId,
+        imported: &mut HashMap<NodeId, NodeId, FxBuildHasher>,
+    ) -> NodeId {
+        // the copy of a node if it doesn't need copies of the children
+        let known = |universe: &mut Self,
+                     imported: &HashMap<NodeId, NodeId, FxBuildHasher>,
+                     node: NodeId| {
+            let source = &other[node];
+            if source.level == 0 {
+                // the leaves are the same in every store
+                Some(node)
+            } else if source.population == 0 {
+                Some(universe.empty_tree(source.level))
             } else {
-                let new_node = self.node_level2_next(&node);
-                node.quick_cache.set(Some(new_node.clone()));
-                return new_node;
-            }
-        }
-
-        let nw = &node.nw;
-        let ne = &node.ne;
-        let sw = &node.sw;
-        let se = &node.se;
-        let n00 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.nw.se,
-            &nw.ne.sw,
-            &nw.sw.ne,
-            &nw.se.nw,
-        );
-        let n01 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.ne.se,
-            &ne.nw.sw,
-            &nw.se.ne,
-            &ne.sw.nw,
-        );
-        let n02 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &ne.nw.se,
-            &ne.ne.sw,
-            &ne.sw.ne,
-            &ne.se.nw,
-        );
-        let n10 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.sw.se,
-            &nw.se.sw,
-            &sw.nw.ne,
-            &sw.ne.nw,
-        );
-        let n11 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw.se.se,
-            &ne.sw.sw,
-            &sw.ne.ne,
-            &se.nw.nw,
-        );
-        let n12 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &ne.sw.se,
-            &ne.se.sw,
-            &se.nw.ne,
-            &se.ne.nw,
-        );
-        let n20 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &sw.nw.se,
-            &sw.ne.sw,
-            &sw.sw.ne,
-            &sw.se.nw,
-        );
-        let n21 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &sw.ne.se,
-            &se.nw.sw,
-            &sw.se.ne,
-            &se.sw.nw,
-        );
-        let n22 = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &se.nw.se,
-            &se.ne.sw,
-            &se.sw.ne,
-            &se.se.nw,
-        );
+                imported.get(&node).copied()
+            }
+        };
+
+        if let Some(copy) = known(self, imported, node) {
+            return copy;
+        }
+
+        // nodes whose children are being copied, with the copies so far
+        let mut stack = vec![(node, [FALSE_LEAF; 4], 0)];
+
+        loop {
+            let (node, copies, len) = stack.last_mut().expect("the first node is popped last");
+
+            if *len < 4 {
+                let child = other[*node].children()[*len];
+                match known(self, imported, child) {
+                    Some(copy) => {
+                        copies[*len] = copy;
+                        *len += 1;
+                    }
+                    None => stack.push((child, [FALSE_LEAF; 4], 0)),
+                }
+                continue;
+            }
+
+            let [nw, ne, sw, se] = *copies;
+            let node = *node;
+            let copy = self.create_tree(nw, ne, sw, se);
+            imported.insert(node, copy);
+            stack.pop();
+
+            let Some((_, copies, len)) = stack.last_mut() else {
+                return copy;
+            };
+            copies[*len] = copy;
+            *len += 1;
+        }
+    }
+
+    // other's root as a node of this universe
+    fn import_other_root(&mut self, other: &LifeUniverse) -> NodeId {
+        if Rc::ptr_eq(&self.hashmap, &other.hashmap) {
+            other.root()
+        } else {
+            let root = other.root();
+            self.import_node(&other.hashmap.borrow(), root, &mut HashMap::default())
+        }
+    }
+
+    // other's root as a node of this universe with the same level as the root
+    fn import_root(&mut self, other: &LifeUniverse) -> NodeId {
+        let node = self.import_other_root(other);
+        let level = self.node(node).level.max(self.node(self.root()).level);
+
+        let root = self.expand_to_level(self.root(), level);
+        self.set_root(root);
+        self.expand_to_level(node, level)
+    }
+
+    // combines the pattern with the pattern of another universe, both centred on the origin
+    #[allow(dead_code)]
+    pub fn combine(&mut self, other: &LifeUniverse, op: BooleanOp) {
+        let node = self.import_root(other);
+        let root = self.node_boolean(op, self.root(), node);
+        self.set_root(root);
+    }
+
+    // the cells that differ from another universe, as a new universe
+    #[allow(dead_code)]
+    pub fn compare_with(&self, other: &LifeUniverse) -> LifeUniverse {
+        let mut result = LifeUniverse::with_store(&self.get_store());
+        result.set_rules(self.rule_s, self.rule_b);
+        let root = result.import_root(self);
+        result.set_root(root);
+        result.combine(other, BooleanOp::Xor);
+        result
+    }
+
+    // the square of size 2^level centred on the origin of another universe,
+    // as a node of this universe
+    fn import_tile(&mut self, tile: &LifeUniverse, level: usize) -> NodeId {
+        let root = self.import_other_root(tile);
+        let mut node = self.expand_to_level(root, level);
+
+        while self.node(node).level > level {
+            let [nw, ne, sw, se] = self.node(node).children().map(|child| self.node(child));
+            node = self.create_tree(nw.se, ne.sw, sw.ne, se.nw);
+        }
+        node
+    }
+
+    // a node of pattern with every cell replaced by one of the tiles
+    fn node_metapattern(
+        &mut self,
+        pattern: &LifeUniverse,
+        node: NodeId,
+        tiles: [NodeId; 2],
+        built: &mut HashMap<NodeId, NodeId, FxBuildHasher>,
+    ) -> NodeId {
+        let known = |built: &HashMap<NodeId, NodeId, FxBuildHasher>, node: NodeId| {
+            let source = pattern.node(node);
+            if source.level == 0 {
+                Some(tiles[source.population])
+            } else {
+                built.get(&node).copied()
+            }
+        };
+
+        if let Some(meta) = known(built, node) {
+            return meta;
+        }
+
+        // nodes whose children are being built, with the results so far
+        let mut stack = vec![(node, [FALSE_LEAF; 4], 0)];
+
+        loop {
+            let (node, metas, len) = stack.last_mut().expect("the first node is popped last");
+
+            if *len < 4 {
+                let child = pattern.node(*node).children()[*len];
+                match known(built, child) {
+                    Some(meta) => {
+                        metas[*len] = meta;
+                        *len += 1;
+                    }
+                    None => stack.push((child, [FALSE_LEAF; 4], 0)),
+                }
+                continue;
+            }
+
+            let [nw, ne, sw, se] = *metas;
+            let node = *node;
+            let meta = self.create_tree(nw, ne, sw, se);
+            built.insert(node, meta);
+            stack.pop();
+
+            let Some((_, metas, len)) = stack.last_mut() else {
+                return meta;
+            };
+            metas[*len] = meta;
+            *len += 1;
+        }
+    }
+
+    // replaces the pattern with a metapattern of another one: every living
+    // cell of pattern becomes the square of size 2^level centred on the origin
+    // of on_tile, every dead one the same square of off_tile (e.g. the OTCA
+    // metapixel with level 11), level is at least 1. Equal parts of pattern share their nodes, so
+    // this takes time in the number of distinct nodes of pattern, not in its area.
+    #[allow(dead_code)]
+    pub fn build_metapattern(
+        &mut self,
+        on_tile: &LifeUniverse,
+        off_tile: &LifeUniverse,
+        pattern: &LifeUniverse,
+        level: usize,
+    ) {
+        self.clear_pattern();
+
+        let level = level.max(1);
+        let tiles = [
+            self.import_tile(off_tile, level),
+            self.import_tile(on_tile, level),
+        ];
+        let root = self.node_metapattern(pattern, pattern.root(), tiles, &mut HashMap::default());
+        self.set_root(root);
+    }
+
+    // a new universe, sharing the store with this one, of every cell that is
+    // alive in any of the next generations (including the current one), e.g.
+    // the rotor and stator of an oscillator. This universe is left as it was.
+    #[allow(dead_code)]
+    pub fn compute_envelope(&mut self, generations: usize) -> LifeUniverse {
+        // kept through the garbage collections of the steps
+        let saved = self.history_entry();
+        self.hashmap.borrow_mut().pin(saved.root);
+        let cell_history = self.cell_history.take();
+        self.set_step(0);
+
+        let mut envelope = self.root();
+        self.hashmap.borrow_mut().pin(envelope);
+
+        for _ in 1..generations {
+            if !self.next_generation(true) {
+                break;
+            }
+
+            let level = self.node(envelope).level.max(self.node(self.root()).level);
+            let current = self.expand_to_level(self.root(), level);
+            let expanded = self.expand_to_level(envelope, level);
+            let union = self.node_boolean(BooleanOp::Union, expanded, current);
+
+            self.hashmap.borrow_mut().unpin(envelope);
+            self.hashmap.borrow_mut().pin(union);
+            envelope = union;
+        }
+
+        self.restore_history_entry(&saved);
+        self.hashmap.borrow_mut().unpin(saved.root);
+        self.cell_history = cell_history;
+
+        let mut universe = Self::with_node_map(self.hashmap.clone());
+        universe.set_rules(self.rule_s, self.rule_b);
+        universe.set_custom_rule(self.custom_rule.clone());
+        universe.set_root(envelope);
+        self.hashmap.borrow_mut().unpin(envelope);
+        universe
+    }
 
-        //debug_assert!(n00.level == n01.level && n00.level == n02.level && n00.level == n10.level && n00.level == n11.level && n00.level == n12.level && n00.level == n20.level && n00.level == n21.level && n00.level == n22.level);
-
-        let n00_n01_n10_n11 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n00, &n01, &n10, &n11);
-        let n01_n02_n11_n12 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n01, &n02, &n11, &n12);
-        let n10_n11_n20_n21 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n10, &n11, &n20, &n21);
-        let n11_n12_n21_n22 =
-            Self::create_tree(&mut self.hashmap, &self.root, &n11, &n12, &n21, &n22);
-
-        let nw_tree = self.node_next_generation(&n00_n01_n10_n11);
-        let ne_tree = self.node_next_generation(&n01_n02_n11_n12);
-        let sw_tree = self.node_next_generation(&n10_n11_n20_n21);
-        let se_tree = self.node_next_generation(&n11_n12_n21_n22);
-
-        let new_node = Self::create_tree(
-            &mut self.hashmap,
-            &self.root,
-            &nw_tree,
-            &ne_tree,
-            &sw_tree,
-            &se_tree,
-        );
+    fn record_cell_history(&mut self, previous: NodeId) {
+        let Some(mut history) = self.cell_history.take() else {
+            return;
+        };
 
-        node.cache.set(Some(new_node.clone()));
-        new_node
+        // all three are centred on the origin, so they line up once they have the same level
+        let level = self
+            .node(previous)
+            .level
+            .max(self.node(self.root()).level)
+            .max(self.node(history.envelope).level);
+        let before = self.expand_to_level(previous, level);
+        let after = self.expand_to_level(self.root(), level);
+        history.expand_to_level(level);
+        history.record(&self.hashmap.borrow(), before, after, self.generation);
+
+        let envelope = self.expand_to_level(history.envelope, level);
+        let envelope = self.node_boolean(BooleanOp::Union, envelope, before);
+        let envelope = self.node_boolean(BooleanOp::Union, envelope, after);
+        self.hashmap.borrow_mut().unpin(history.envelope);
+        self.hashmap.borrow_mut().pin(envelope);
+        history.envelope = envelope;
+
+        self.cell_history = Some(history);
     }
 
+    // starts recording which cells are alive in every generation from now on,
+    // stepping is single generation only while recording
     #[allow(dead_code)]
-    pub fn next_generation(&mut self, is_single: bool) {
-        /*unsafe {
-            COLLISION_COUNT = 0;
-        }*/
-        let mut root = self.root.clone();
+    pub fn start_cell_history(&mut self) {
+        self.stop_cell_history();
+        self.set_step(0);
+
+        let envelope = self.root();
+        self.hashmap.borrow_mut().pin(envelope);
+        let level = self.node(envelope).level;
+        self.cell_history = Some(CellHistory::new(envelope, level, self.generation));
+    }
 
-        while (is_single && root.level <= self.step + 2)
-            || root.nw.population != root.nw.se.se.population
-            || root.ne.population != root.ne.sw.sw.population
-            || root.sw.population != root.sw.ne.ne.population
-            || root.se.population != root.se.nw.nw.population
-        {
-            root = self.expand_universe(root);
+    #[allow(dead_code)]
+    pub fn stop_cell_history(&mut self) {
+        if let Some(history) = self.cell_history.take() {
+            self.hashmap.borrow_mut().unpin(history.envelope);
         }
+    }
 
-        // superstep button doesn't exist
-        /*if is_single {
-            self.generation += Self::pow2(self.step);
-            root = self.node_next_generation(root);
-        } else {
-            self.generation += Self::pow2(self.root.level - 2);
-            root = self.node_quick_next_generation(root);
-        }*/
-        self.generation += Self::pow2(self.step);
-        root = self.node_next_generation(&root);
+    #[allow(dead_code)]
+    pub fn has_cell_history(&self) -> bool {
+        self.cell_history.is_some()
+    }
 
-        // log(format!("Collision count: {}", unsafe { COLLISION_COUNT }).as_str());
+    #[allow(dead_code)]
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.hashmap.borrow_mut().memory_limit = bytes;
+        self.hashmap.borrow_mut().out_of_memory = false;
+    }
+
+    #[allow(dead_code)]
+    pub fn get_memory_limit(&self) -> usize {
+        self.hashmap.borrow().memory_limit
+    }
 
-        self.root = root;
+    // [estimated bytes, limit, nodes, hash table size, cache flushes, out of memory]
+    #[allow(dead_code)]
+    pub fn get_memory_stats(&self) -> Vec<f64> {
+        let hashmap = self.hashmap.borrow();
+        vec![
+            hashmap.memory_usage() as f64,
+            hashmap.memory_limit as f64,
+            hashmap.len as f64,
+            hashmap.table.len() as f64,
+            hashmap.stats.cache_flushes as f64,
+            if hashmap.out_of_memory { 1.0 } else { 0.0 },
+        ]
     }
 
     fn get_bounds(&self, field_x: &Vec<i32>, field_y: &Vec<i32>) -> Bounds {
@@ -957,7 +2932,7 @@ impl LifeUniverse {
         end: usize,
         field_x: &mut Vec<i32>,
         field_y: &mut Vec<i32>,
-    ) -> Rc<TreeNode> {
+    ) -> NodeId {
         let mut set = 0;
         // log("Start level2_setup");
         for i in start..=end {
@@ -968,18 +2943,11 @@ impl LifeUniverse {
             set |= 1 << (x & 1 | (y & 1 | x & 2) << 1 | (y & 2) << 2);
         }
 
-        if let Some(cached) = &self.level2_cache[set] {
-            cached.clone()
-        } else {
-            let nw = self.level1_create(set);
-            let ne = self.level1_create(set >> 4);
-            let sw = self.level1_create(set >> 8);
-            let se = self.level1_create(set >> 12);
-
-            let new_node = Self::create_tree(&mut self.hashmap, &self.root, &nw, &ne, &sw, &se);
-
-            self.level2_cache[set].insert(new_node).clone()
-        }
+        let nw = self.level1_create(set);
+        let ne = self.level1_create(set >> 4);
+        let sw = self.level1_create(set >> 8);
+        let se = self.level1_create(set >> 12);
+        self.create_tree(nw, ne, sw, se)
     }
 
     fn setup_field_recurse(
@@ -989,12 +2957,12 @@ impl LifeUniverse {
         field_x: &mut Vec<i32>,
         field_y: &mut Vec<i32>,
         mut level: usize,
-    ) -> Rc<TreeNode> {
+    ) -> NodeId {
         // log(format!("From recurse: current level is: {}", level).as_str());
         if start > end || end == usize::MAX
         /* wrapped around */
         {
-            return Self::empty_tree(&mut self.empty_tree_cache, &self.false_leaf, &mut self.hashmap, &self.root, level).clone();
+            return self.empty_tree(level);
         }
 
         if level == 2 {
@@ -1019,7 +2987,7 @@ impl LifeUniverse {
 
         // log("From recurse: creating tree...");
 
-        Self::create_tree(&mut self.hashmap, &self.root, &nw, &ne, &sw, &se)
+        self.create_tree(nw, ne, sw, se)
     }
 
     #[allow(dead_code)]
@@ -1049,7 +3017,8 @@ impl LifeUniverse {
 
         self.move_field(&mut field_x, &mut field_y, offset, offset);
 
-        self.root = self.setup_field_recurse(0, count - 1, &mut field_x, &mut field_y, level);
+        let root = self.setup_field_recurse(0, count - 1, &mut field_x, &mut field_y, level);
+        self.set_root(root);
     }
 
     #[allow(dead_code)]
@@ -1057,14 +3026,18 @@ impl LifeUniverse {
         self.step
     }
 
+    // steps advance by 2^step generations. Returns the step that is used,
+    // which is 0 while cell history is recorded, as that can't skip
+    // generations.
     #[allow(dead_code)]
-    pub fn set_step(&mut self, step: usize) {
+    pub fn set_step(&mut self, step: usize) -> usize {
+        let step = if self.cell_history.is_some() { 0 } else { step };
+
         if step != self.step {
             self.step = step;
-
-            self.uncache(false);
-            self.reset_caches();
+            self.cancel_step();
         }
+        step
     }
 
     #[allow(dead_code)]
@@ -1073,11 +3046,84 @@ impl LifeUniverse {
             self.rule_s = s;
             self.rule_b = b;
 
-            self.uncache(true);
-            self.reset_caches();
+            self.cancel_step();
+        }
+        self.set_custom_rule(None);
+    }
+
+    fn set_custom_rule(&mut self, rule: Option<CustomRule>) {
+        if self.custom_rule.as_ref().map(CustomRule::id) != rule.as_ref().map(CustomRule::id) {
+            self.custom_rule = rule;
+            self.cancel_step();
+        }
+    }
+
+    // replaces the rule with the @TABLE or @TREE of a .rule file, the error
+    // names the line that couldn't be read
+    #[allow(dead_code)]
+    pub fn set_rule_table(&mut self, source: &str) -> Result<(), String> {
+        let table = RuleTable::parse(source).map_err(|error| error.to_string())?;
+        self.set_custom_rule(Some(CustomRule::Table(Rc::new(table))));
+        Ok(())
+    }
+
+    // sets a life-like rule like B36/S23 or 23/36, or a larger than life rule
+    // like R5,C0,M1,S34..58,B34..45,NM. The error says where and why the rule
+    // can't be used, the rule is unchanged then.
+    #[allow(dead_code)]
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        match Rule::parse(rule).map_err(|error| error.to_string())? {
+            Rule::LifeLike { s, b } => self.set_rules(s, b),
+            Rule::LargerThanLife(rule) => {
+                self.set_custom_rule(Some(CustomRule::LargerThanLife(Rc::new(rule))))
+            }
+        }
+        Ok(())
+    }
+
+    // the rule in the notation of set_rule, or the name of a rule table
+    #[allow(dead_code)]
+    pub fn get_rule(&self) -> String {
+        match &self.custom_rule {
+            Some(rule) => rule.name(),
+            None => Rule::LifeLike {
+                s: self.rule_s,
+                b: self.rule_b,
+            }
+            .to_string(),
         }
     }
 
+    // back to the rule given by get_rule_s and get_rule_b
+    #[allow(dead_code)]
+    pub fn clear_custom_rule(&mut self) {
+        self.set_custom_rule(None);
+    }
+
+    #[allow(dead_code)]
+    pub fn has_custom_rule(&self) -> bool {
+        self.custom_rule.is_some()
+    }
+
+    // rule tables can't be set again from the name get_rule gives them
+    #[allow(dead_code)]
+    pub fn has_rule_table(&self) -> bool {
+        matches!(self.custom_rule, Some(CustomRule::Table(_)))
+    }
+
+    #[allow(dead_code)]
+    pub fn get_custom_rule_name(&self) -> String {
+        self.custom_rule
+            .as_ref()
+            .map_or(String::new(), CustomRule::name)
+    }
+
+    // number of cell states of the rule, 2 for life-like rules
+    #[allow(dead_code)]
+    pub fn get_states(&self) -> usize {
+        self.custom_rule.as_ref().map_or(2, CustomRule::states)
+    }
+
     #[allow(dead_code)]
     pub fn get_rule_s(&self) -> usize {
         self.rule_s
@@ -1088,71 +3134,44 @@ impl LifeUniverse {
         self.rule_b
     }
 
+    // calls emit with the screen position and size of every visible node that is
+    // either a single cell or at most one pixel large
     fn draw_node(
-        node: &Rc<TreeNode>,
-        data: &mut Vec<f64>,
-        x: f64,
-        y: f64,
-        size: f64,
-        offset_x: f64,
-        offset_y: f64,
-        height: f64,
+        hashmap: &NodeMap,
+        node: NodeId,
+        view: &View,
         width: f64,
+        height: f64,
+        mut emit: impl FnMut(&TreeNode, f64, f64, f64),
     ) {
-        // log(format!("Drawing node... Population: {}, Level: {}", node.population, node.level).as_str());
-        if node.population == 0
-            || x + size + offset_x < 0.0
-            || y + size + offset_y < 0.0
-            || x + offset_x >= width
-            || y + offset_y >= height
-        {
-            // don't draw outside of screen
-            return;
-        }
+        let (offset_x, offset_y) = (view.offset_x, view.offset_y);
+        let mut stack = vec![(node, view.x, view.y, view.size)];
+
+        while let Some((node, x, y, size)) = stack.pop() {
+            let node = &hashmap[node];
+            // log(format!("Drawing node... Population: {}, Level: {}", node.population, node.level).as_str());
+            if node.population == 0
+                || x + size + offset_x < 0.0
+                || y + size + offset_y < 0.0
+                || x + offset_x >= width
+                || y + offset_y >= height
+            {
+                // don't draw outside of screen
+                continue;
+            }
 
-        if size <= 1.0 || node.level == 0 {
-            // no need to check if population is 0, because we already did that earlier
-            data.push(x + offset_x);
-            data.push(y + offset_y);
-        } else {
-            let size = size / 2.0;
+            if size <= 1.0 || node.level == 0 {
+                // no need to check if population is 0, because we already did that earlier
+                emit(node, x + offset_x, y + offset_y, size);
+            } else {
+                let size = size / 2.0;
 
-            Self::draw_node(
-                &node.nw, data, x, y, size, offset_x, offset_y, height, width,
-            );
-            Self::draw_node(
-                &node.ne,
-                data,
-                x + size,
-                y,
-                size,
-                offset_x,
-                offset_y,
-                height,
-                width,
-            );
-            Self::draw_node(
-                &node.sw,
-                data,
-                x,
-                y + size,
-                size,
-                offset_x,
-                offset_y,
-                height,
-                width,
-            );
-            Self::draw_node(
-                &node.se,
-                data,
-                x + size,
-                y + size,
-                size,
-                offset_x,
-                offset_y,
-                height,
-                width,
-            );
+                // pushed in reverse, so that nw is drawn first
+                stack.push((node.se, x + size, y + size, size));
+                stack.push((node.sw, x, y + size, size));
+                stack.push((node.ne, x + size, y, size));
+                stack.push((node.nw, x, y, size));
+            }
         }
     }
 
@@ -1169,12 +3188,540 @@ impl LifeUniverse {
     ) -> Vec<f64> {
         let mut data = Vec::new();
         // log(format!("Starting draw with: x: {}, y: {}, size: {}, offset_x: {}, offset_y: {}, height: {}, width: {}", x, y, size, offset_x, offset_y, height, width).as_str());
+        let root = self.root();
+        Self::draw_node(
+            &self.hashmap.borrow(),
+            root,
+            &View::new(x, y, size, offset_x, offset_y),
+            width,
+            height,
+            |_, x, y, _| {
+                data.push(x);
+                data.push(y);
+            },
+        );
+        data
+    }
+
+    // like draw, but returns triples of x, y and the population density of the node
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn draw_density(
+        &self,
+        x: f64,
+        y: f64,
+        size: f64,
+        height: f64,
+        width: f64,
+        offset_x: f64,
+        offset_y: f64,
+    ) -> Vec<f64> {
+        let mut data = Vec::new();
+        let root = self.root();
+        Self::draw_node(
+            &self.hashmap.borrow(),
+            root,
+            &View::new(x, y, size, offset_x, offset_y),
+            width,
+            height,
+            |node, x, y, _| {
+                data.push(x);
+                data.push(y);
+                data.push(node.population as f64 / Self::pow2(2 * node.level));
+            },
+        );
+        data
+    }
+
+    // like draw, but returns triples of x, y and the state of the cell. Nodes
+    // that are drawn as a single pixel get state 1.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn draw_states(
+        &self,
+        x: f64,
+        y: f64,
+        size: f64,
+        height: f64,
+        width: f64,
+        offset_x: f64,
+        offset_y: f64,
+    ) -> Vec<f64> {
+        let mut data = Vec::new();
+        let root = self.root();
+        Self::draw_node(
+            &self.hashmap.borrow(),
+            root,
+            &View::new(x, y, size, offset_x, offset_y),
+            width,
+            height,
+            |node, x, y, _| {
+                data.push(x);
+                data.push(y);
+                // the children of a leaf are the leaf itself
+                data.push(if node.level == 0 {
+                    node.nw.state() as f64
+                } else {
+                    1.0
+                });
+            },
+        );
+        data
+    }
+
+    // like draw, but for every cell that has been alive since start_cell_history.
+    // Returns triples of x, y and the number of generations since the cell last
+    // changed (counting the current one), negative if it's dead now. Nodes that
+    // are drawn as a single pixel get 0.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn draw_cell_history(
+        &self,
+        x: f64,
+        y: f64,
+        size: f64,
+        height: f64,
+        width: f64,
+        offset_x: f64,
+        offset_y: f64,
+    ) -> Vec<f64> {
+        let mut data = Vec::new();
+        let Some(history) = &self.cell_history else {
+            return data;
+        };
+
+        // the envelope is centred like the root, but can have another level
+        let root = self.root();
+        let hashmap = self.hashmap.borrow();
+        let cell_size = size / Self::pow2(hashmap[root].level);
+        let envelope_size = cell_size * Self::pow2(hashmap[history.envelope].level);
+        let left = x - (envelope_size - size) / 2.0;
+        let top = y - (envelope_size - size) / 2.0;
+        let half = Self::pow2(hashmap[history.envelope].level - 1);
+
         Self::draw_node(
-            &self.root, &mut data, x, y, size, offset_x, offset_y, height, width,
+            &hashmap,
+            history.envelope,
+            &View::new(left, top, envelope_size, offset_x, offset_y),
+            width,
+            height,
+            |node, screen_x, screen_y, _| {
+                let mut age = 0.0;
+
+                if node.level == 0 {
+                    let cell_x = ((screen_x - offset_x - left) / cell_size).round() - half;
+                    let cell_y = ((screen_y - offset_y - top) / cell_size).round() - half;
+                    age = self.generation - history.last_change(cell_x, cell_y) + 1.0;
+                    if !self.get_bit(cell_x, cell_y) {
+                        age = -age;
+                    }
+                }
+
+                data.push(screen_x);
+                data.push(screen_y);
+                data.push(age);
+            },
         );
         data
     }
 
+    // fraction of living cells under each pixel, for shading zoomed out views.
+    // The buffer has to hold width * height pixels.
+    #[allow(dead_code)]
+    pub fn render_density(
+        &self,
+        buffer: &mut [f32],
+        width: usize,
+        height: usize,
+        view: &View,
+    ) -> Result<(), String> {
+        check_buffer(buffer.len(), width, height)?;
+        self.render_node_density(self.root(), buffer, width, height, view);
+        Ok(())
+    }
+
+    fn render_node_density(
+        &self,
+        root: NodeId,
+        buffer: &mut [f32],
+        width: usize,
+        height: usize,
+        view: &View,
+    ) {
+        buffer.fill(0.0);
+
+        Self::draw_node(
+            &self.hashmap.borrow(),
+            root,
+            view,
+            width as f64,
+            height as f64,
+            |node, x, y, size| {
+                if node.level == 0 && size > 1.0 {
+                    // zoomed in, this is a single living cell
+                    let x0 = x.max(0.0) as usize;
+                    let y0 = y.max(0.0) as usize;
+                    let x1 = ((x + size) as usize).min(width);
+                    let y1 = ((y + size) as usize).min(height);
+
+                    for row in y0..y1 {
+                        buffer[row * width + x0..row * width + x1.max(x0)].fill(1.0);
+                    }
+                } else if x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height {
+                    // the node covers size * size of the pixel
+                    let pixel = &mut buffer[y as usize * width + x as usize];
+                    let density = node.population as f64 / Self::pow2(2 * node.level);
+                    *pixel = (*pixel + (density * size * size) as f32).min(1.0);
+                }
+            },
+        );
+    }
+
+    fn fill_square(
+        buffer: &mut [u32],
+        width: usize,
+        height: usize,
+        x: f64,
+        y: f64,
+        size: f64,
+        color: u32,
+    ) {
+        // same clipping as fill_square in draw.js
+        let mut x = x as i64;
+        let mut y = y as i64;
+        let mut w = size as i64;
+        let mut h = w;
+
+        if x < 0 {
+            w += x;
+            x = 0;
+        }
+        if x + w > width as i64 {
+            w = width as i64 - x;
+        }
+        if y < 0 {
+            h += y;
+            y = 0;
+        }
+        if y + h > height as i64 {
+            h = height as i64 - y;
+        }
+        if w <= 0 || h <= 0 {
+            return;
+        }
+
+        for row in y..y + h {
+            let start = row as usize * width + x as usize;
+            buffer[start..start + w as usize].fill(color);
+        }
+    }
+
+    // draws straight into an image buffer (as in ImageData, so 0xAABBGGRR on
+    // little endian) of width * height pixels
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn render_rgba(
+        &self,
+        buffer: &mut [u32],
+        width: usize,
+        height: usize,
+        view: &View,
+        cell_color: u32,
+        background: u32,
+        border_width: f64,
+    ) -> Result<(), String> {
+        check_buffer(buffer.len(), width, height)?;
+        buffer.fill(background);
+
+        let root = self.root();
+        Self::draw_node(
+            &self.hashmap.borrow(),
+            root,
+            view,
+            width as f64,
+            height as f64,
+            |_, x, y, size| {
+                Self::fill_square(
+                    buffer,
+                    width,
+                    height,
+                    x,
+                    y,
+                    size.max(1.0) - border_width,
+                    cell_color,
+                );
+            },
+        );
+        Ok(())
+    }
+
+    fn blend(background: u32, color: u32, density: f32) -> [u8; 3] {
+        let channel = |shift: u32| {
+            let from = (background >> shift & 0xFF) as f32;
+            let to = (color >> shift & 0xFF) as f32;
+            (from + (to - from) * density).round() as u8
+        };
+
+        [channel(16), channel(8), channel(0)]
+    }
+
+    // renders the rows from band_top on of the given rectangle, as many as
+    // fit into density
+    #[allow(clippy::too_many_arguments)]
+    fn render_band(
+        &self,
+        root: NodeId,
+        left: f64,
+        top: f64,
+        image_width: usize,
+        band_top: usize,
+        scale: f64,
+        density: &mut [f32],
+    ) {
+        let half = Self::pow2(self.node(root).level - 1);
+        let view = View::new(
+            (-half - left) * scale,
+            (-half - top) * scale - band_top as f64,
+            2.0 * half * scale,
+            0.0,
+            0.0,
+        );
+        self.render_node_density(
+            root,
+            density,
+            image_width,
+            density.len() / image_width,
+            &view,
+        );
+    }
+
+    // renders the cells in the given rectangle in bands of rows, so that huge
+    // images never need to be in memory at once
+    fn render_region(
+        &self,
+        left: f64,
+        top: f64,
+        image_width: usize,
+        image_height: usize,
+        scale: f64,
+        mut band: impl FnMut(&[f32]),
+    ) {
+        let root = self.root();
+        let mut density = vec![0.0; image_width * BAND_HEIGHT];
+
+        for band_top in (0..image_height).step_by(BAND_HEIGHT) {
+            let band_height = BAND_HEIGHT.min(image_height - band_top);
+            let density = &mut density[..image_width * band_height];
+            self.render_band(root, left, top, image_width, band_top, scale, density);
+            band(density);
+        }
+    }
+
+    // starts a png of the cells in the given rectangle, scale is in pixels
+    // per cell and colors are 0xRRGGBB. Zoomed out areas are shaded by their
+    // density. The image shows the pattern as it is now, even if it changes
+    // before the png is done.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn begin_png(
+        &mut self,
+        left: f64,
+        top: f64,
+        width: f64,
+        height: f64,
+        scale: f64,
+        cell_color: u32,
+        background: u32,
+    ) {
+        self.cancel_png();
+
+        let image_width = (width * scale).ceil().max(1.0) as usize;
+        let image_height = (height * scale).ceil().max(1.0) as usize;
+        let mut encoder = png::PngEncoder::new(image_width, image_height);
+
+        encoder.text("Title", &self.info.name);
+        encoder.text("Author", &self.info.author);
+        encoder.text("Description", &self.info.comments.join("\n"));
+        encoder.text("URL", &self.info_urls().join("\n"));
+
+        // kept through the garbage collections of later steps
+        let root = self.root();
+        self.hashmap.borrow_mut().pin(root);
+
+        self.pending_png = Some(PendingPng {
+            root,
+            encoder,
+            left,
+            top,
+            scale,
+            image_width,
+            image_height,
+            cell_color,
+            background,
+            rows: 0,
+        });
+    }
+
+    // the next bytes of the png started by begin_png, one band of rows at a
+    // time, and undefined once the whole png has been returned
+    #[allow(dead_code)]
+    pub fn continue_png(&mut self) -> Option<Vec<u8>> {
+        let mut pending = self.pending_png.take()?;
+
+        if pending.rows == pending.image_height {
+            self.hashmap.borrow_mut().unpin(pending.root);
+            return Some(pending.encoder.finish());
+        }
+
+        let band_height = BAND_HEIGHT.min(pending.image_height - pending.rows);
+        let mut density = vec![0.0; pending.image_width * band_height];
+        self.render_band(
+            pending.root,
+            pending.left,
+            pending.top,
+            pending.image_width,
+            pending.rows,
+            pending.scale,
+            &mut density,
+        );
+
+        let mut rows = Vec::with_capacity((pending.image_width * 3 + 1) * band_height);
+        for row in density.chunks(pending.image_width) {
+            rows.push(0); // no filter
+            for &d in row {
+                rows.extend_from_slice(&Self::blend(pending.background, pending.cell_color, d));
+            }
+        }
+        pending.encoder.write_rows(&rows);
+        pending.rows += band_height;
+
+        let bytes = pending.encoder.take();
+        self.pending_png = Some(pending);
+        Some(bytes)
+    }
+
+    // the whole png of begin_png at once. A png that is being written with
+    // continue_png carries on afterwards.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn export_png(
+        &mut self,
+        left: f64,
+        top: f64,
+        width: f64,
+        height: f64,
+        scale: f64,
+        cell_color: u32,
+        background: u32,
+    ) -> Vec<u8> {
+        let pending = self.pending_png.take();
+        self.begin_png(left, top, width, height, scale, cell_color, background);
+
+        let mut png = vec![];
+        while let Some(bytes) = self.continue_png() {
+            png.extend(bytes);
+        }
+
+        self.pending_png = pending;
+        png
+    }
+
+    #[allow(dead_code)]
+    pub fn cancel_png(&mut self) {
+        if let Some(pending) = self.pending_png.take() {
+            self.hashmap.borrow_mut().unpin(pending.root);
+        }
+    }
+
+    // animated gif of the given rectangle, advancing 2^step generations between
+    // frames. The universe is left as it was.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn export_animation(
+        &mut self,
+        left: f64,
+        top: f64,
+        width: f64,
+        height: f64,
+        scale: f64,
+        frames: usize,
+        step: usize,
+        fps: f64,
+        cell_color: u32,
+        background: u32,
+    ) -> Vec<u8> {
+        let image_width = (width * scale).ceil().clamp(1.0, u16::MAX as f64) as usize;
+        let image_height = (height * scale).ceil().clamp(1.0, u16::MAX as f64) as usize;
+        let delay = (100.0 / fps).round().clamp(1.0, u16::MAX as f64) as u16;
+
+        let palette =
+            std::array::from_fn(|i| Self::blend(background, cell_color, i as f32 / 255.0));
+        let mut encoder = gif::GifEncoder::new(image_width as u16, image_height as u16, &palette);
+        let mut pixels = Vec::new();
+        encoder.comment(&self.info_text());
+
+        // kept through the garbage collections of the steps
+        let saved = self.history_entry();
+        self.hashmap.borrow_mut().pin(saved.root);
+        let cell_history = self.cell_history.take();
+        self.set_step(step);
+
+        for frame in 0..frames {
+            if frame > 0 && !self.next_generation(true) {
+                break;
+            }
+
+            encoder.begin_frame(delay);
+            self.render_region(left, top, image_width, image_height, scale, |density| {
+                pixels.clear();
+                pixels.extend(density.iter().map(|&d| (d * 255.0).round() as u8));
+                encoder.write_pixels(&pixels);
+            });
+        }
+
+        self.restore_history_entry(&saved);
+        self.hashmap.borrow_mut().unpin(saved.root);
+        self.cell_history = cell_history;
+        encoder.finish()
+    }
+
+    // [nodes, hash table size, cached results, quick cached results, gc count, gc time in ms,
+    //  cache hits, cache misses, nodes at level 0, 1, 2, ...]. Nodes of dead and
+    //  living cells below level 3 are fixed and not counted.
+    #[allow(dead_code)]
+    pub fn get_stats(&self) -> Vec<f64> {
+        let mut cached = 0;
+        let mut quick_cached = 0;
+        let root = self.root();
+        let hashmap = self.hashmap.borrow();
+        let mut levels = vec![0; hashmap[root].level + 1];
+
+        for node in hashmap.live_nodes() {
+            if node.cache.is_some() {
+                cached += 1;
+            }
+            if node.quick_cache.is_some() {
+                quick_cached += 1;
+            }
+            if node.level >= levels.len() {
+                levels.resize(node.level + 1, 0);
+            }
+            levels[node.level] += 1;
+        }
+
+        let stats = &hashmap.stats;
+        let mut ret = vec![
+            hashmap.len as f64,
+            hashmap.table.len() as f64,
+            cached as f64,
+            quick_cached as f64,
+            stats.gc_count as f64,
+            stats.gc_time,
+            stats.cache_hits as f64,
+            stats.cache_misses as f64,
+        ];
+        ret.extend(levels.into_iter().map(|n| n as f64));
+        ret
+    }
+
+    #[allow(dead_code)]
+    pub fn reset_stats(&mut self) {
+        self.hashmap.borrow_mut().stats = Stats::default();
+    }
+
     #[allow(dead_code)]
     pub fn get_generation(&self) -> f64 {
         self.generation
@@ -1182,11 +3729,29 @@ impl LifeUniverse {
 
     #[allow(dead_code)]
     pub fn get_population(&self) -> usize {
-        self.root.population
+        self.node(self.root()).population
     }
 
     #[allow(dead_code)]
     pub fn get_level(&self) -> usize {
-        self.root.level
+        self.node(self.root()).level
+    }
+}
+
+impl Drop for LifeUniverse {
+    // unpins everything, the store may outlive this universe
+    fn drop(&mut self) {
+        self.clear_history();
+        self.stop_cell_history();
+        self.cancel_png();
+
+        let mut hashmap = self.hashmap.borrow_mut();
+        for (_, entry) in &self.snapshots {
+            hashmap.unpin(entry.root);
+        }
+        if let Some(rewind) = self.rewind_state {
+            hashmap.unpin(rewind);
+        }
+        hashmap.unpin(self.root.get());
     }
 }
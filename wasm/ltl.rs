@@ -0,0 +1,348 @@
+// Larger than Life rules, like R5,C0,M1,S34..58,B34..45,NM. Cells count the
+// living cells within a range of up to MAX_RANGE cells, on a square (NM), a
+// diamond (NN) or a circle (NC). With C above 2 cells that don't survive
+// decay through the states 2 to C-1 before they die, and only state 1 counts
+// as living.
+//
+// Cells far away affect each other, so the base case of hashlife is a block
+// large enough to step its centre by one generation.
+
+use super::MAX_STATES;
+use super::rule::{Reason, RuleError, error};
+use std::ops::RangeInclusive;
+
+pub const MAX_RANGE: usize = 500;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Moore,
+    VonNeumann,
+    Circular,
+}
+
+// the buffers of next_block, kept between calls so that stepping doesn't
+// allocate a block for every node
+#[derive(Default)]
+pub struct Scratch {
+    // the block to step, row by row
+    pub cells: Vec<u8>,
+    // living cells of every row left of each column
+    counts: Vec<u32>,
+    // the centre half one generation later
+    pub next: Vec<u8>,
+}
+
+pub struct LargerThanLife {
+    pub range: usize,
+    pub states: usize,
+    // whether a cell counts itself
+    middle: bool,
+    survival: RangeInclusive<usize>,
+    birth: RangeInclusive<usize>,
+    shape: Shape,
+    // identifies the rule among the cached results of the node store
+    pub id: usize,
+}
+
+fn parse_number(text: &str, position: usize, what: &str) -> Result<usize, RuleError> {
+    text.parse().or_else(|_| {
+        error(
+            position,
+            Reason::InvalidPart(format!("{what} has to be a number, got '{text}'")),
+        )
+    })
+}
+
+// min..max or a single count
+fn parse_counts(
+    text: &str,
+    position: usize,
+    what: &str,
+) -> Result<RangeInclusive<usize>, RuleError> {
+    let (min, max) = text.split_once("..").unwrap_or((text, text));
+    let (min, max) = (
+        parse_number(min, position, what)?,
+        parse_number(max, position, what)?,
+    );
+
+    if min > max {
+        let message = format!("{what} {min}..{max} is empty");
+        return error(position, Reason::InvalidPart(message));
+    }
+    Ok(min..=max)
+}
+
+impl LargerThanLife {
+    pub fn parse(rule: &str) -> Result<LargerThanLife, RuleError> {
+        let mut range = None;
+        let mut states = 2;
+        let mut middle = false;
+        let mut survival = None;
+        let mut birth = None;
+        let mut shape = Shape::Moore;
+        // characters before the current part
+        let mut position = 0;
+        let end = rule.trim_end().chars().count();
+
+        for part in rule.trim_end().split(',') {
+            let start = position + part.chars().take_while(|c| c.is_whitespace()).count();
+            position += part.chars().count() + 1;
+
+            let part = part.trim();
+            let Some(key) = part.chars().next() else {
+                return error(start, Reason::Expected("a part like R5"));
+            };
+            let value = &part[key.len_utf8()..];
+            let invalid = |message: String| error(start, Reason::InvalidPart(message));
+
+            match key.to_ascii_uppercase() {
+                'R' => {
+                    let r = parse_number(value, start, "the range")?;
+                    if !(1..=MAX_RANGE).contains(&r) {
+                        return invalid(format!("the range has to be between 1 and {MAX_RANGE}"));
+                    }
+                    range = Some(r);
+                }
+                'C' => {
+                    states = parse_number(value, start, "the number of states")?.max(2);
+                    if states > MAX_STATES {
+                        return invalid(format!("at most {MAX_STATES} states"));
+                    }
+                }
+                'M' => {
+                    middle = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return invalid(format!("M has to be 0 or 1, got '{value}'")),
+                    }
+                }
+                'S' => survival = Some(parse_counts(value, start, "survival")?),
+                'B' => {
+                    let counts = parse_counts(value, start, "birth")?;
+                    if *counts.start() == 0 {
+                        // the empty space has to stay empty
+                        return error(start, Reason::BirthWithoutNeighbours);
+                    }
+                    birth = Some(counts);
+                }
+                'N' => {
+                    shape = match value.to_ascii_uppercase().as_str() {
+                        "M" => Shape::Moore,
+                        "N" => Shape::VonNeumann,
+                        "C" => Shape::Circular,
+                        _ => return invalid(format!("unsupported neighbourhood 'N{value}'")),
+                    }
+                }
+                _ => return error(start, Reason::UnknownSuffix(part.to_string())),
+            }
+        }
+
+        let missing = |what: &str| error(end, Reason::InvalidPart(format!("{what} is missing")));
+        let Some(range) = range else {
+            return missing("the range R");
+        };
+        let Some(survival) = survival else {
+            return missing("survival S");
+        };
+        let Some(birth) = birth else {
+            return missing("birth B");
+        };
+
+        Ok(LargerThanLife {
+            range,
+            states,
+            middle,
+            survival,
+            birth,
+            shape,
+            id: super::ruletable::next_id(),
+        })
+    }
+
+    // the rule as it is written by parse, like R5,C0,M1,S34..58,B34..45,NM
+    pub fn name(&self) -> String {
+        let shape = match self.shape {
+            Shape::Moore => 'M',
+            Shape::VonNeumann => 'N',
+            Shape::Circular => 'C',
+        };
+
+        format!(
+            "R{},C{},M{},S{}..{},B{}..{},N{}",
+            self.range,
+            if self.states == 2 { 0 } else { self.states },
+            self.middle as u8,
+            self.survival.start(),
+            self.survival.end(),
+            self.birth.start(),
+            self.birth.end(),
+            shape,
+        )
+    }
+
+    // level of the smallest nodes whose centre can be stepped by a
+    // generation, their size is at least four times the range
+    pub fn base_level(&self) -> usize {
+        2 + self.range.next_power_of_two().trailing_zeros() as usize
+    }
+
+    // how far the neighbourhood reaches to the left and right, by row
+    fn widths(&self) -> Vec<usize> {
+        let range = self.range as isize;
+
+        (-range..=range)
+            .map(|dy| {
+                let dy = dy.unsigned_abs();
+                match self.shape {
+                    Shape::Moore => self.range,
+                    Shape::VonNeumann => self.range - dy,
+                    Shape::Circular => {
+                        // the cells whose centre is within range + 1/2
+                        let limit = self.range * self.range + self.range - dy * dy;
+                        (0..=self.range).rev().find(|w| w * w <= limit).unwrap_or(0)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // steps the size by size cells of scratch.cells, the centre half one
+    // generation later goes into scratch.next row by row
+    pub fn next_block(&self, scratch: &mut Scratch, size: usize) {
+        let Scratch {
+            cells,
+            counts,
+            next,
+        } = scratch;
+
+        counts.clear();
+        counts.resize(size * (size + 1), 0);
+        for y in 0..size {
+            for x in 0..size {
+                let row = y * (size + 1);
+                counts[row + x + 1] = counts[row + x] + (cells[y * size + x] == 1) as u32;
+            }
+        }
+
+        let widths = self.widths();
+        let (start, end) = (size / 4, size / 4 * 3);
+        next.clear();
+
+        for y in start..end {
+            for x in start..end {
+                let state = cells[y * size + x];
+                let mut living = 0;
+
+                for (i, &width) in widths.iter().enumerate() {
+                    let row = (y + i - self.range) * (size + 1);
+                    living += counts[row + x + width + 1] - counts[row + x - width];
+                }
+                if state == 1 && !self.middle {
+                    living -= 1;
+                }
+
+                let living = living as usize;
+                next.push(match state {
+                    0 => self.birth.contains(&living) as u8,
+                    1 if self.survival.contains(&living) => 1,
+                    // decaying
+                    _ if (state as usize + 1) < self.states => state + 1,
+                    _ => 0,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LifeUniverse;
+    use crate::tests::{soup, states_of};
+    use std::collections::{HashMap, HashSet};
+
+    // one generation, counting every cell of the neighbourhood on its own
+    fn naive_next(
+        rule: &LargerThanLife,
+        cells: &HashMap<(i64, i64), u8>,
+    ) -> HashMap<(i64, i64), u8> {
+        let r = rule.range as i64;
+        let inside = |dx: i64, dy: i64| match rule.shape {
+            Shape::Moore => true,
+            Shape::VonNeumann => dx.abs() + dy.abs() <= r,
+            Shape::Circular => dx * dx + dy * dy <= r * r + r,
+        };
+
+        let mut candidates = HashSet::new();
+        for &(x, y) in cells.keys() {
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    candidates.insert((x + dx, y + dy));
+                }
+            }
+        }
+
+        let mut next = HashMap::new();
+        for (x, y) in candidates {
+            let state = cells.get(&(x, y)).copied().unwrap_or(0);
+            let mut living = 0;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let counted = (dx, dy) != (0, 0) || rule.middle;
+                    if counted && inside(dx, dy) && cells.get(&(x + dx, y + dy)) == Some(&1) {
+                        living += 1;
+                    }
+                }
+            }
+
+            let state = match state {
+                0 => rule.birth.contains(&living) as u8,
+                1 if rule.survival.contains(&living) => 1,
+                _ if (state as usize + 1) < rule.states => state + 1,
+                _ => 0,
+            };
+            if state != 0 {
+                next.insert((x, y), state);
+            }
+        }
+        next
+    }
+
+    #[test]
+    fn steps_match_naive() {
+        let rules = [
+            "R2,C0,M0,S3..6,B4..5,NM",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "R3,C0,M1,S5..10,B5..7,NN",
+            "R3,C0,M1,S8..16,B9..12,NC",
+            "R2,C4,M0,S3..6,B4..5,NM",
+            "R3,C5,M1,S6..12,B6..9,NC",
+        ];
+
+        for (seed, text) in rules.into_iter().enumerate() {
+            let rule = LargerThanLife::parse(text).ok().unwrap();
+            let mut life = LifeUniverse::new();
+            life.set_rule(text).unwrap();
+
+            let mut expected = HashMap::new();
+            for (x, y) in soup(seed as u64 + 1, 24, 24) {
+                life.set_cell_state(x as f64, y as f64, 1);
+                expected.insert((x as i64, y as i64), 1);
+            }
+
+            for _ in 0..5 {
+                life.next_generation(true);
+                expected = naive_next(&rule, &expected);
+                assert!(states_of(&life) == expected, "{text}");
+            }
+
+            // larger steps go through nodes above the base level
+            life.set_step(2);
+            life.next_generation(true);
+            for _ in 0..4 {
+                expected = naive_next(&rule, &expected);
+            }
+            assert!(states_of(&life) == expected, "{text}");
+        }
+    }
+}
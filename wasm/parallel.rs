@@ -0,0 +1,469 @@
+// Steps large nodes on several threads, native targets only. For the length of
+// a step the node store is shared read-only by every thread, and the nodes and
+// results they compute go into a concurrent arena on top of it. Arena ids
+// continue after the last index of the store, so a NodeId names the same node
+// on every thread and results found by one thread are used by all others.
+// Once the step is done the new nodes and results are moved into the store,
+// where later steps find them.
+//
+// Like the stepper of the universe, each node steps its nine overlapping parts
+// and then its four quadrants. From FORK_LEVEL on these go into a queue shared
+// by the threads: idle threads take the oldest and largest from its front,
+// while the thread that queued them works through them from the back and
+// helps with others until the ones that were taken are done.
+
+use super::{LifeUniverse, NodeId, NodeMap, bitboard_next, spread_rows};
+use rustc_hash::FxBuildHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::{mem, thread};
+
+// nodes below this level are stepped on the calling thread
+const MIN_LEVEL: usize = 10;
+// nodes from this level on queue their parts for the other threads
+const FORK_LEVEL: usize = 7;
+const CHUNK_BITS: usize = 16;
+const SHARDS: usize = 64;
+
+// chunks of arena nodes, allocated as they fill up
+type Chunks = Box<[OnceLock<Box<[OnceLock<Node>]>>]>;
+type Shard = Mutex<HashMap<[NodeId; 4], NodeId, FxBuildHasher>>;
+
+// results found for nodes of the store, two per node like Node::results. The
+// universe keeps them between steps, only the slots that were set are reset.
+pub struct StoreResults {
+    slots: Vec<AtomicU32>,
+    // indices of the slots that were set, split like the arena tables
+    set: [Mutex<Vec<u32>>; SHARDS],
+}
+
+impl Default for StoreResults {
+    fn default() -> StoreResults {
+        StoreResults {
+            slots: vec![],
+            set: std::array::from_fn(|_| Mutex::default()),
+        }
+    }
+}
+
+impl StoreResults {
+    // the results that were set as (slot, result), leaving every slot 0
+    fn drain(&mut self) -> impl Iterator<Item = (usize, u32)> {
+        let slots = &self.slots;
+        self.set.iter_mut().flat_map(move |set| {
+            let set = mem::take(set.get_mut().expect("no thread panicked"));
+            set.into_iter()
+                .map(|i| (i as usize, slots[i as usize].swap(0, Ordering::Relaxed)))
+        })
+    }
+}
+
+struct Node {
+    children: [NodeId; 4],
+    population: usize,
+    level: usize,
+    // the result of a step and of a quick step, 0 while unknown
+    results: [AtomicU32; 2],
+}
+
+struct Arena<'a> {
+    store: &'a NodeMap,
+    // the index of the first arena node, one past the last one of the store
+    first: usize,
+    chunks: Chunks,
+    len: AtomicUsize,
+    // arena nodes that fit into the memory limit
+    room: usize,
+    out_of_memory: AtomicBool,
+    // arena nodes by their children, split by hash so that threads creating
+    // nodes rarely wait for each other
+    tables: Box<[Shard]>,
+    store_results: &'a StoreResults,
+    // nodes to step and whether the step is quick
+    queue: Mutex<VecDeque<(NodeId, bool)>>,
+    queued: Condvar,
+    finished: AtomicBool,
+    rule_s: usize,
+    rule_b: usize,
+    step: usize,
+    misses: AtomicUsize,
+}
+
+fn to_id(index: u32) -> NodeId {
+    NodeId(NonZeroU32::new(index).expect("results are nodes"))
+}
+
+impl<'a> Arena<'a> {
+    fn new(
+        universe: &LifeUniverse,
+        store: &'a NodeMap,
+        store_results: &'a StoreResults,
+    ) -> Arena<'a> {
+        let first = store.nodes.len();
+        let chunks = ((u32::MAX as usize - first) >> CHUNK_BITS) + 1;
+        // the bytes a node takes in the store, with the slots the table grows by
+        let room = match store.memory_limit {
+            0 => usize::MAX,
+            limit => {
+                limit.saturating_sub(store.memory_usage())
+                    / (NodeMap::NODE_BYTES + 2 * NodeMap::SLOT_BYTES)
+            }
+        };
+
+        Arena {
+            store,
+            first,
+            chunks: (0..chunks).map(|_| OnceLock::new()).collect(),
+            len: AtomicUsize::new(0),
+            room,
+            out_of_memory: AtomicBool::new(false),
+            tables: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            store_results,
+            queue: Mutex::default(),
+            queued: Condvar::new(),
+            finished: AtomicBool::new(false),
+            rule_s: universe.rule_s,
+            rule_b: universe.rule_b,
+            step: universe.step,
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    fn arena_node(&self, id: NodeId) -> &Node {
+        let index = id.index() - self.first;
+        let chunk = self.chunks[index >> CHUNK_BITS]
+            .get()
+            .expect("chunk of a created node");
+        chunk[index & ((1 << CHUNK_BITS) - 1)]
+            .get()
+            .expect("created node")
+    }
+
+    // children, population and level
+    fn node(&self, id: NodeId) -> ([NodeId; 4], usize, usize) {
+        if id.index() < self.first {
+            let node = &self.store[id];
+            (node.children(), node.population, node.level)
+        } else {
+            let node = self.arena_node(id);
+            (node.children, node.population, node.level)
+        }
+    }
+
+    fn result(&self, id: NodeId, quick: bool) -> Option<NodeId> {
+        let result = if id.index() < self.first {
+            let node = &self.store[id];
+            let cached = if quick { node.quick_cache } else { node.cache };
+            if cached.is_some() {
+                return cached;
+            }
+            &self.store_results.slots[id.index() * 2 + quick as usize]
+        } else {
+            &self.arena_node(id).results[quick as usize]
+        };
+        NonZeroU32::new(result.load(Ordering::Acquire)).map(NodeId)
+    }
+
+    fn set_result(&self, id: NodeId, quick: bool, result: NodeId) {
+        if id.index() < self.first {
+            let i = id.index() * 2 + quick as usize;
+            let slot = &self.store_results.slots[i];
+            // only the first thread to find the result remembers the slot
+            if slot
+                .compare_exchange(0, result.0.get(), Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let set = &self.store_results.set[i % SHARDS];
+                set.lock().expect("no thread panicked").push(i as u32);
+            }
+        } else {
+            self.arena_node(id).results[quick as usize].store(result.0.get(), Ordering::Release);
+        }
+    }
+
+    fn create(&self, children: [NodeId; 4]) -> NodeId {
+        if let Some(id) = NodeId::fixed(children) {
+            return id;
+        }
+
+        // nodes with a child in the arena are in the arena too
+        if children.iter().all(|child| child.index() < self.first)
+            && let Ok(id) = self.store.slot(children)
+        {
+            return id;
+        }
+
+        let shard = FxBuildHasher.hash_one(children) as usize % SHARDS;
+        let mut table = self.tables[shard].lock().expect("no thread panicked");
+        if let Some(&id) = table.get(&children) {
+            return id;
+        }
+
+        let nodes = children.map(|child| self.node(child));
+        let node = Node {
+            children,
+            population: nodes.iter().map(|n| n.1).sum(),
+            level: nodes[0].2 + 1,
+            results: Default::default(),
+        };
+
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        if index >= self.room {
+            // the step is abandoned, see Arena::step
+            self.out_of_memory.store(true, Ordering::Relaxed);
+        }
+        let chunk = self.chunks[index >> CHUNK_BITS]
+            .get_or_init(|| (0..1 << CHUNK_BITS).map(|_| OnceLock::new()).collect());
+        let _ = chunk[index & ((1 << CHUNK_BITS) - 1)].set(node);
+
+        let id = u32::try_from(self.first + index).expect("fewer than 2^32 nodes");
+        let id = to_id(id);
+        table.insert(children, id);
+        id
+    }
+
+    // the 8x8 cells of a level 3 node, like LifeUniverse::level3_bits
+    fn bits(&self, node: NodeId) -> u64 {
+        let level1 = |n: NodeId| {
+            let [nw, ne, sw, se] = self.node(n).0.map(|leaf| (leaf.state() != 0) as u64);
+            nw | ne << 1 | sw << 4 | se << 5
+        };
+        let level2 = |n: NodeId| {
+            let bits = n.level2_bits().unwrap_or_else(|| {
+                let [nw, ne, sw, se] = self.node(n).0.map(level1);
+                nw | ne << 2 | sw << 8 | se << 10
+            });
+            spread_rows(bits)
+        };
+
+        let [nw, ne, sw, se] = self.node(node).0.map(level2);
+        nw | ne << 4 | sw << 32 | se << 36
+    }
+
+    // the results of stepping the nodes, shared with the other threads for
+    // nodes of high levels
+    fn step_all<const N: usize>(
+        &self,
+        level: usize,
+        nodes: [NodeId; N],
+        quick: bool,
+    ) -> [NodeId; N] {
+        if level < FORK_LEVEL {
+            return nodes.map(|node| self.step(node, quick));
+        }
+
+        let mut queue = self.queue.lock().expect("no thread panicked");
+        queue.extend(nodes.map(|node| (node, quick)));
+        drop(queue);
+        self.queued.notify_all();
+
+        let mut results = nodes;
+        for (i, &node) in nodes.iter().enumerate().rev() {
+            let mut queue = self.queue.lock().expect("no thread panicked");
+            let queued = queue.iter().rposition(|&task| task == (node, quick));
+            let taken = queued.and_then(|i| queue.remove(i)).is_none();
+            drop(queue);
+
+            results[i] = if taken {
+                self.wait(node, quick)
+            } else {
+                self.step(node, quick)
+            };
+        }
+        results
+    }
+
+    // the result of a node another thread is stepping, helping with the
+    // queue in the meantime
+    fn wait(&self, node: NodeId, quick: bool) -> NodeId {
+        let (children, _, level) = self.node(node);
+        let quick = quick || self.step == level - 2;
+
+        loop {
+            if let Some(result) = self.result(node, quick) {
+                return result;
+            }
+            if self.out_of_memory.load(Ordering::Relaxed) {
+                return children[0];
+            }
+
+            let task = self.queue.lock().expect("no thread panicked").pop_back();
+            match task {
+                Some((node, quick)) => {
+                    self.step(node, quick);
+                }
+                None => thread::yield_now(),
+            }
+        }
+    }
+
+    // what the other threads do until the step is finished
+    fn work(&self) {
+        let mut queue = self.queue.lock().expect("no thread panicked");
+        while !self.finished.load(Ordering::Relaxed) {
+            match queue.pop_front() {
+                Some((node, quick)) => {
+                    drop(queue);
+                    self.step(node, quick);
+                    queue = self.queue.lock().expect("no thread panicked");
+                }
+                None => queue = self.queued.wait(queue).expect("no thread panicked"),
+            }
+        }
+    }
+
+    fn step(&self, node: NodeId, quick: bool) -> NodeId {
+        let (children, _, level) = self.node(node);
+        let quick = quick || self.step == level - 2;
+
+        if let Some(result) = self.result(node, quick) {
+            return result;
+        }
+        if self.out_of_memory.load(Ordering::Relaxed) {
+            // any node of the right level will do, none of them is kept
+            return children[0];
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let result = if level == 3 {
+            let mut board = bitboard_next(self.bits(node), self.rule_b, self.rule_s);
+            if quick {
+                board = bitboard_next(board, self.rule_b, self.rule_s);
+            }
+            LifeUniverse::level2_from_bits(board, 2, 2)
+        } else {
+            let grandchildren = self.node(node).0.map(|child| self.node(child).0);
+            let [[a, b, c, d], [e, f, g, h], [i, j, k, l], [m, n, o, p]] = grandchildren;
+            // by row and column, like LifeUniverse::grandchildren
+            let g = [[a, b, e, f], [c, d, g, h], [i, j, m, n], [k, l, o, p]];
+            let sub = |i: usize| {
+                let (row, col) = (i / 3, i % 3);
+                [
+                    g[row][col],
+                    g[row][col + 1],
+                    g[row + 1][col],
+                    g[row + 1][col + 1],
+                ]
+            };
+
+            let parts: [NodeId; 9] = if quick {
+                // advancing at this level, the nine overlapping subnodes are stepped first
+                let nodes = std::array::from_fn(|i| self.create(sub(i)));
+                self.step_all(level, nodes, true)
+            } else {
+                // not advancing at this level, the nine subnodes are just centred
+                std::array::from_fn(|i| {
+                    let [nw, ne, sw, se] = sub(i).map(|n| self.node(n).0);
+                    self.create([nw[3], ne[2], sw[1], se[0]])
+                })
+            };
+
+            let nodes = [[0, 1, 3, 4], [1, 2, 4, 5], [3, 4, 6, 7], [4, 5, 7, 8]]
+                .map(|[nw, ne, sw, se]| self.create([parts[nw], parts[ne], parts[sw], parts[se]]));
+            let quadrants = self.step_all(level, nodes, quick);
+            self.create(quadrants)
+        };
+
+        self.set_result(node, quick, result);
+        result
+    }
+}
+
+// like node_step, but with the threads given to set_threads. Steps that don't
+// fit into the memory limit are left to node_step, which collects garbage as
+// it goes.
+pub fn node_step(universe: &mut LifeUniverse, node: NodeId, quick: bool) -> Option<NodeId> {
+    // the arena steps life-like rules only
+    if universe.threads < 2
+        || universe.node(node).level < MIN_LEVEL
+        || universe.custom_rule.is_some()
+    {
+        return None;
+    }
+
+    let mut store_results = mem::take(&mut universe.store_results);
+    let store = universe.hashmap.borrow();
+    let first = store.nodes.len();
+    if store_results.slots.len() < first * 2 {
+        store_results
+            .slots
+            .resize_with(first * 2, || AtomicU32::new(0));
+    }
+
+    let arena = Arena::new(universe, &store, &store_results);
+    let result = thread::scope(|scope| {
+        for _ in 1..universe.threads {
+            scope.spawn(|| arena.work());
+        }
+        let result = arena.step(node, quick);
+
+        let _queue = arena.queue.lock().expect("no thread panicked");
+        arena.finished.store(true, Ordering::Relaxed);
+        arena.queued.notify_all();
+        result
+    });
+
+    let Arena {
+        chunks,
+        len,
+        out_of_memory,
+        misses,
+        ..
+    } = arena;
+    drop(store);
+
+    if out_of_memory.into_inner() {
+        // the results found so far may rest on the abandoned ones
+        for _ in store_results.drain() {}
+        universe.store_results = store_results;
+        return None;
+    }
+
+    // the new nodes in the order they were created, so children come first
+    let nodes = chunks
+        .into_iter()
+        .filter_map(OnceLock::into_inner)
+        .flat_map(|chunk| chunk.into_iter().filter_map(OnceLock::into_inner))
+        .take(len.into_inner());
+
+    let mut hashmap = universe.hashmap.borrow_mut();
+    let mut ids: Vec<NodeId> = vec![];
+    let in_store = |id: NodeId, ids: &[NodeId]| {
+        if id.index() < first {
+            id
+        } else {
+            ids[id.index() - first]
+        }
+    };
+    let mut results = vec![];
+
+    for node in nodes {
+        let [nw, ne, sw, se] = node.children.map(|child| in_store(child, &ids));
+        let id = hashmap.create_tree(nw, ne, sw, se);
+        ids.push(id);
+        results.push((id, node.results.map(AtomicU32::into_inner)));
+    }
+
+    let set = store_results.drain().map(|(i, result)| {
+        let mut both = [0; 2];
+        both[i % 2] = result;
+        (to_id((i / 2) as u32), both)
+    });
+
+    for (id, [cache, quick_cache]) in results.into_iter().chain(set) {
+        if cache != 0 {
+            hashmap[id].cache = Some(in_store(to_id(cache), &ids));
+        }
+        if quick_cache != 0 {
+            hashmap[id].quick_cache = Some(in_store(to_id(quick_cache), &ids));
+        }
+    }
+
+    hashmap.stats.cache_misses += misses.into_inner();
+    drop(hashmap);
+    universe.store_results = store_results;
+    Some(in_store(result, &ids))
+}
@@ -0,0 +1,553 @@
+// Minimal streaming png encoder. Rows are compressed as they come in, with a
+// small lz77 matcher and the fixed huffman codes of deflate, which works well
+// enough for the large uniform areas of life patterns.
+
+const WINDOW: usize = 1 << 15;
+const HASH_SIZE: usize = 1 << 15;
+const MAX_CHAIN: usize = 32;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+
+    for part in parts {
+        for &b in *part {
+            crc = CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+    }
+
+    !crc
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // huffman codes are stored starting with the most significant bit
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+}
+
+pub struct Deflater {
+    writer: BitWriter,
+    // the last WINDOW bytes of input, followed by the bytes being compressed
+    buffer: Vec<u8>,
+    // stream position of buffer[0]
+    base: usize,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    adler_a: u32,
+    adler_b: u32,
+}
+
+impl Deflater {
+    pub fn new() -> Deflater {
+        let mut writer = BitWriter {
+            out: Vec::new(),
+            bits: 0,
+            count: 0,
+        };
+
+        // zlib header: deflate with a 32k window, no dictionary
+        writer.write(0x78, 8);
+        writer.write(0x01, 8);
+
+        Deflater {
+            writer,
+            buffer: Vec::new(),
+            base: 0,
+            head: vec![usize::MAX; HASH_SIZE],
+            prev: vec![usize::MAX; WINDOW],
+            adler_a: 1,
+            adler_b: 0,
+        }
+    }
+
+    fn literal(&mut self, lit: u32) {
+        match lit {
+            0..=143 => self.writer.write_code(0x30 + lit, 8),
+            144..=255 => self.writer.write_code(0x190 + lit - 144, 9),
+            256..=279 => self.writer.write_code(lit - 256, 7),
+            _ => self.writer.write_code(0xC0 + lit - 280, 8),
+        }
+    }
+
+    fn copy(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|&b| b as usize <= length)
+            .unwrap_or(0);
+        self.literal(257 + code as u32);
+        self.writer.write(
+            (length - LENGTH_BASE[code] as usize) as u32,
+            LENGTH_EXTRA[code] as u32,
+        );
+
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|&b| b as usize <= distance)
+            .unwrap_or(0);
+        self.writer.write_code(code as u32, 5);
+        self.writer.write(
+            (distance - DISTANCE_BASE[code] as usize) as u32,
+            DISTANCE_EXTRA[code] as u32,
+        );
+    }
+
+    fn hash(bytes: &[u8]) -> usize {
+        ((bytes[0] as usize) << 10 ^ (bytes[1] as usize) << 5 ^ bytes[2] as usize) & (HASH_SIZE - 1)
+    }
+
+    fn insert(&mut self, i: usize) {
+        let h = Self::hash(&self.buffer[i..]);
+        let pos = self.base + i;
+        self.prev[pos % WINDOW] = self.head[h];
+        self.head[h] = pos;
+    }
+
+    fn longest_match(&self, i: usize) -> (usize, usize) {
+        let pos = self.base + i;
+        let max = MAX_MATCH.min(self.buffer.len() - i);
+        let mut candidate = self.head[Self::hash(&self.buffer[i..])];
+        let mut best = (0, 0);
+
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || candidate < self.base || pos - candidate > WINDOW {
+                break;
+            }
+
+            let j = candidate - self.base;
+            let length = self.buffer[i..i + max]
+                .iter()
+                .zip(&self.buffer[j..])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if length > best.0 {
+                best = (length, pos - candidate);
+                if length == max {
+                    break;
+                }
+            }
+
+            let previous = self.prev[candidate % WINDOW];
+            if previous == usize::MAX || previous >= candidate {
+                break;
+            }
+            candidate = previous;
+        }
+
+        best
+    }
+
+    // compresses data as one block and returns the finished bytes
+    pub fn write(&mut self, data: &[u8]) -> Vec<u8> {
+        for &b in data {
+            self.adler_a = (self.adler_a + b as u32) % 65521;
+            self.adler_b = (self.adler_b + self.adler_a) % 65521;
+        }
+
+        let mut i = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+
+        // not the last block, fixed huffman codes
+        self.writer.write(0, 1);
+        self.writer.write(1, 2);
+
+        while i < self.buffer.len() {
+            let mut length = 0;
+            let mut distance = 0;
+
+            if i + MIN_MATCH <= self.buffer.len() {
+                (length, distance) = self.longest_match(i);
+                self.insert(i);
+            }
+
+            if length >= MIN_MATCH {
+                self.copy(length, distance);
+
+                for j in i + 1..(i + length).min(self.buffer.len() - MIN_MATCH + 1) {
+                    self.insert(j);
+                }
+                i += length;
+            } else {
+                self.literal(self.buffer[i] as u32);
+                i += 1;
+            }
+        }
+
+        self.literal(256);
+
+        if self.buffer.len() > WINDOW {
+            let drop = self.buffer.len() - WINDOW;
+            self.buffer.drain(..drop);
+            self.base += drop;
+        }
+
+        std::mem::take(&mut self.writer.out)
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        // empty final block
+        self.writer.write(1, 1);
+        self.writer.write(1, 2);
+        self.literal(256);
+        self.writer.align();
+
+        let adler = self.adler_b << 16 | self.adler_a;
+        self.writer.out.extend_from_slice(&adler.to_be_bytes());
+        std::mem::take(&mut self.writer.out)
+    }
+}
+
+pub struct PngEncoder {
+    out: Vec<u8>,
+    deflater: Deflater,
+}
+
+impl PngEncoder {
+    // 8 bit rgb image
+    pub fn new(width: usize, height: usize) -> PngEncoder {
+        let mut encoder = PngEncoder {
+            out: b"\x89PNG\r\n\x1a\n".to_vec(),
+            deflater: Deflater::new(),
+        };
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        encoder.chunk(b"IHDR", &header);
+
+        encoder
+    }
+
+    pub fn chunk(&mut self, kind: &[u8; 4], data: &[u8]) {
+        self.out
+            .extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.out.extend_from_slice(kind);
+        self.out.extend_from_slice(data);
+        self.out
+            .extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+    }
+
+    // utf-8 text, like the title or author of the image. Empty text is left out.
+    pub fn text(&mut self, keyword: &str, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        // no compression, no language
+        let mut data = keyword.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(text.as_bytes());
+        self.chunk(b"iTXt", &data);
+    }
+
+    // rgb rows, each one prefixed with its filter byte
+    pub fn write_rows(&mut self, rows: &[u8]) {
+        let compressed = self.deflater.write(rows);
+        if !compressed.is_empty() {
+            self.chunk(b"IDAT", &compressed);
+        }
+    }
+
+    // the bytes of the png since the last call, so that it can be passed on
+    // in parts
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.out)
+    }
+
+    // the rest of the png
+    pub fn finish(mut self) -> Vec<u8> {
+        let compressed = self.deflater.finish();
+        self.chunk(b"IDAT", &compressed);
+        self.chunk(b"IEND", &[]);
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LifeUniverse;
+    use crate::tests::{load, soup};
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        // position in bits
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: usize) -> usize {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = self.data[self.position / 8] >> (self.position % 8) & 1;
+                value |= (bit as usize) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        // huffman codes start with their most significant bit
+        fn code(&mut self, count: usize) -> usize {
+            (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        fn literal(&mut self) -> usize {
+            let code = self.code(7);
+            if code < 24 {
+                return 256 + code;
+            }
+            let code = code << 1 | self.bits(1);
+            match code {
+                0x30..0xC0 => code - 0x30,
+                0xC0..0xC8 => code - 0xC0 + 280,
+                _ => (code << 1 | self.bits(1)) - 0x190 + 144,
+            }
+        }
+    }
+
+    // a zlib stream of stored and fixed huffman blocks, checking its header
+    // and adler-32
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[0] & 0x0F, 8, "deflate");
+        assert_eq!((data[0] as u32 * 256 + data[1] as u32) % 31, 0);
+
+        let mut reader = BitReader {
+            data: &data[2..],
+            position: 0,
+        };
+        let mut out: Vec<u8> = vec![];
+
+        loop {
+            let last = reader.bits(1);
+            match reader.bits(2) {
+                0 => {
+                    reader.position = reader.position.div_ceil(8) * 8;
+                    let length = reader.bits(16);
+                    assert_eq!(reader.bits(16), !length & 0xFFFF);
+                    for _ in 0..length {
+                        out.push(reader.bits(8) as u8);
+                    }
+                }
+                1 => loop {
+                    let symbol = reader.literal();
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+
+                    let code = symbol - 257;
+                    let length =
+                        LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as usize);
+                    let code = reader.code(5);
+                    let distance =
+                        DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code] as usize);
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                },
+                kind => panic!("unexpected block type {kind}"),
+            }
+            if last == 1 {
+                break;
+            }
+        }
+
+        let end = 2 + reader.position.div_ceil(8);
+        assert_eq!(data[end..], adler32(&out).to_be_bytes());
+        out
+    }
+
+    fn adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        b << 16 | a
+    }
+
+    // the chunks of a png, checking their crc
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+            let data = &rest[8..8 + length];
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&[&kind, data]));
+            chunks.push((kind, data));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // "li" and then "fe" as the final block
+        let mut data = vec![0x78, 0x01, 0, 2, 0, 0xFD, 0xFF, b'l', b'i'];
+        data.extend_from_slice(&[1, 2, 0, 0xFD, 0xFF, b'f', b'e']);
+        data.extend_from_slice(&adler32(b"life").to_be_bytes());
+
+        assert_eq!(inflate(&data), b"life");
+    }
+
+    #[test]
+    fn deflate() {
+        // repeats further apart than the window and matches across writes
+        let mut state = 1u32;
+        let mut data = vec![];
+        for i in 0..100_000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let random = i % 7000 < 3000;
+            data.push(if random {
+                (state >> 24) as u8
+            } else {
+                i as u8 % 5
+            });
+        }
+
+        let mut deflater = Deflater::new();
+        let mut compressed = vec![];
+        for part in data.chunks(10_000) {
+            compressed.extend(deflater.write(part));
+        }
+        compressed.extend(deflater.finish());
+
+        assert!(inflate(&compressed) == data);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut life = LifeUniverse::new();
+        load(&mut life, &soup(3, 40, 100));
+        let (cell_color, background) = (0xFF8000, 0x000020);
+
+        // taller than a band, one pixel per cell
+        life.begin_png(-20.0, -50.0, 40.0, 100.0, 1.0, cell_color, background);
+        let mut png = vec![];
+        while let Some(bytes) = life.continue_png() {
+            png.extend(bytes);
+        }
+
+        let chunks = chunks(&png);
+        assert_eq!(&chunks[0].0, b"IHDR");
+        assert_eq!(chunks[0].1, [0, 0, 0, 40, 0, 0, 0, 100, 8, 2, 0, 0, 0]);
+        assert_eq!(&chunks.last().unwrap().0, b"IEND");
+
+        let compressed: Vec<u8> = chunks
+            .iter()
+            .filter(|chunk| &chunk.0 == b"IDAT")
+            .flat_map(|chunk| chunk.1.iter().copied())
+            .collect();
+        let rows = inflate(&compressed);
+        assert_eq!(rows.len(), 100 * (40 * 3 + 1));
+
+        for (y, row) in rows.chunks(40 * 3 + 1).enumerate() {
+            assert_eq!(row[0], 0);
+            for (x, pixel) in row[1..].chunks(3).enumerate() {
+                let alive = life.get_bit(x as f64 - 20.0, y as f64 - 50.0);
+                let color = if alive { cell_color } else { background };
+                assert_eq!(pixel, &color.to_be_bytes()[1..], "{x} {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn export_png() {
+        let mut life = LifeUniverse::new();
+        load(&mut life, &soup(4, 40, 100));
+
+        // exported while the same png is written a band at a time
+        life.begin_png(-20.0, -50.0, 40.0, 100.0, 1.0, 0xFFFFFF, 0);
+        let mut png = life.continue_png().unwrap();
+        let exported = life.export_png(-20.0, -50.0, 40.0, 100.0, 1.0, 0xFFFFFF, 0);
+        while let Some(bytes) = life.continue_png() {
+            png.extend(bytes);
+        }
+        assert!(png == exported);
+
+        // the pattern of a png that is never finished is unpinned with its universe
+        let pins = |life: &LifeUniverse| life.hashmap.borrow().pinned.values().sum::<usize>();
+        let before = pins(&life);
+        let mut other = LifeUniverse::with_store(&life.get_store());
+        load(&mut other, &soup(5, 40, 40));
+        other.begin_png(-20.0, -20.0, 40.0, 40.0, 1.0, 0xFFFFFF, 0);
+        assert!(pins(&life) > before);
+        drop(other);
+        assert_eq!(pins(&life), before);
+    }
+}
@@ -0,0 +1,116 @@
+// Steps a pattern one generation at a time in tiles of 64x64 cells, without
+// any hashing. Hashlife only pays off when parts of the pattern repeat, on
+// chaotic patterns like soups this is much faster.
+
+use super::next_cells;
+use rustc_hash::FxBuildHasher;
+use std::collections::HashMap;
+
+pub const TILE_BITS: usize = 6;
+
+// 64 rows, bit i of a row is the cell in column i
+pub type Tile = [u64; 64];
+
+pub struct QuickLife {
+    // only tiles with living cells, by tile column and row
+    pub tiles: HashMap<(i64, i64), Box<Tile>, FxBuildHasher>,
+}
+
+impl QuickLife {
+    pub fn new() -> QuickLife {
+        QuickLife {
+            tiles: HashMap::default(),
+        }
+    }
+
+    // the tiles from the origin to the farthest tile
+    pub fn extent(&self) -> i64 {
+        let distance = |&(x, y): &(i64, i64)| x.max(y).max(-x - 1).max(-y - 1);
+        self.tiles.keys().map(distance).max().unwrap_or(0)
+    }
+
+    pub fn step(&mut self, rule_b: usize, rule_s: usize) {
+        let mut candidates: Vec<(i64, i64)> = Vec::with_capacity(self.tiles.len() * 2);
+
+        for (&(x, y), tile) in &self.tiles {
+            candidates.push((x, y));
+
+            // neighbouring tiles can only come alive next to living cells
+            let left = tile.iter().any(|row| row & 1 != 0);
+            let right = tile.iter().any(|row| row >> 63 != 0);
+            let top = tile[0] != 0;
+            let bottom = tile[63] != 0;
+
+            let edges = [
+                (-1, 0, left),
+                (1, 0, right),
+                (0, -1, top),
+                (0, 1, bottom),
+                (-1, -1, tile[0] & 1 != 0),
+                (1, -1, tile[0] >> 63 != 0),
+                (-1, 1, tile[63] & 1 != 0),
+                (1, 1, tile[63] >> 63 != 0),
+            ];
+
+            for (dx, dy, alive) in edges {
+                if alive && !self.tiles.contains_key(&(x + dx, y + dy)) {
+                    candidates.push((x + dx, y + dy));
+                }
+            }
+        }
+
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut next = HashMap::with_capacity_and_hasher(candidates.len(), Default::default());
+
+        for (x, y) in candidates {
+            let tile = self.tile_next(x, y, rule_b, rule_s);
+            if tile.iter().any(|&row| row != 0) {
+                next.insert((x, y), tile);
+            }
+        }
+
+        self.tiles = next;
+    }
+
+    fn tile_next(&self, x: i64, y: i64, rule_b: usize, rule_s: usize) -> Box<Tile> {
+        let get = |dx: i64, dy: i64| self.tiles.get(&(x + dx, y + dy)).map(|t| &**t);
+        let around = [
+            [get(-1, -1), get(0, -1), get(1, -1)],
+            [get(-1, 0), get(0, 0), get(1, 0)],
+            [get(-1, 1), get(0, 1), get(1, 1)],
+        ];
+
+        // a row and the same row shifted by one column each way, with the
+        // cells coming in from the tiles to the left and right
+        let row = |r: i64| {
+            let (tiles, r) = match r {
+                -1 => (&around[0], 63),
+                64 => (&around[2], 0),
+                _ => (&around[1], r as usize),
+            };
+            let cells = |t: Option<&Tile>| t.map_or(0, |t| t[r]);
+            let (left, centre, right) = (cells(tiles[0]), cells(tiles[1]), cells(tiles[2]));
+
+            (centre << 1 | left >> 63, centre, centre >> 1 | right << 63)
+        };
+
+        let mut next = Box::new([0; 64]);
+        let mut above = row(-1);
+        let mut current = row(0);
+
+        for (r, out) in next.iter_mut().enumerate() {
+            let below = row(r as i64 + 1);
+            let neighbours = [
+                above.0, above.1, above.2, current.0, current.2, below.0, below.1, below.2,
+            ];
+            *out = next_cells(neighbours, current.1, rule_b, rule_s);
+
+            above = current;
+            current = below;
+        }
+
+        next
+    }
+}
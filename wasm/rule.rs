@@ -0,0 +1,502 @@
+// Parses rule strings: life-like rules in B/S notation like B36/S23, in S/B
+// notation like 23/36, and larger than life rules like
+// R5,C0,M1,S34..58,B34..45,NM. Errors tell where and why a rule can't be
+// read, so that the UI can explain rules it doesn't know.
+//
+// Ranges of life-like rules, like B3/S23..B36/S236 or B3x/S2x, are read for
+// going through many rules at once.
+
+use super::ltl::LargerThanLife;
+use std::fmt;
+
+pub enum Rule {
+    // bit n is set if n living neighbours let a cell survive or be born
+    LifeLike { s: usize, b: usize },
+    LargerThanLife(LargerThanLife),
+}
+
+pub enum Reason {
+    Empty,
+    // a neighbour count listed twice, like the 3 in B33/S23
+    DuplicateDigit(char),
+    // a neighbour count above 8
+    DigitTooLarge(char),
+    // text after a rule that isn't part of it
+    UnknownSuffix(String),
+    // rules of another family, like hexagonal or Generations rules
+    UnsupportedFamily(&'static str),
+    Expected(&'static str),
+    BirthWithoutNeighbours,
+    // a part of a larger than life rule that is out of range or missing
+    InvalidPart(String),
+}
+
+pub struct RuleError {
+    // characters before the problem
+    pub position: usize,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Empty => write!(f, "the rule is empty"),
+            Reason::DuplicateDigit(digit) => write!(f, "digit {digit} is listed twice"),
+            Reason::DigitTooLarge(digit) => write!(f, "digit {digit} is more than 8 neighbours"),
+            Reason::UnknownSuffix(suffix) => write!(f, "unknown suffix '{suffix}'"),
+            Reason::UnsupportedFamily(family) => write!(f, "{family} aren't supported"),
+            Reason::Expected(what) => write!(f, "expected {what}"),
+            Reason::BirthWithoutNeighbours => {
+                write!(
+                    f,
+                    "B0 rules aren't supported, the empty space would come alive"
+                )
+            }
+            Reason::InvalidPart(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at character {}", self.reason, self.position + 1)
+    }
+}
+
+pub fn error<T>(position: usize, reason: Reason) -> Result<T, RuleError> {
+    Err(RuleError { position, reason })
+}
+
+// letters of isotropic non-totalistic rules like B2n3/S23-q
+const HENSEL_LETTERS: &str = "cekainyqjrtwz-";
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    // where a 0 was given for birth
+    birth_zero: Option<usize>,
+    // whether an x may follow the digits, and whether it did for survival
+    // and birth
+    wildcards: bool,
+    any: (bool, bool),
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    // the neighbour counts at the current position as a bit mask
+    fn digits(&mut self, birth: bool) -> Result<usize, RuleError> {
+        let mut mask = 0;
+
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            let digit = c as usize - '0' as usize;
+            if digit > 8 {
+                return error(self.position, Reason::DigitTooLarge(c));
+            }
+            if mask & 1 << digit != 0 {
+                return error(self.position, Reason::DuplicateDigit(c));
+            }
+            if birth && digit == 0 {
+                self.birth_zero = Some(self.position);
+            }
+            mask |= 1 << digit;
+            self.position += 1;
+        }
+
+        if self.wildcards && self.peek().is_some_and(|c| c.eq_ignore_ascii_case(&'x')) {
+            self.position += 1;
+            if birth {
+                self.any.1 = true;
+            } else {
+                self.any.0 = true;
+            }
+        }
+
+        if self
+            .peek()
+            .is_some_and(|c| HENSEL_LETTERS.contains(c.to_ascii_lowercase()))
+        {
+            return error(
+                self.position,
+                Reason::UnsupportedFamily("isotropic non-totalistic rules"),
+            );
+        }
+        Ok(mask)
+    }
+
+    fn slash(&mut self) -> bool {
+        let found = self.peek() == Some('/');
+        self.position += found as usize;
+        found
+    }
+
+    // B3/S23, S23/B3, B3S23 and the like
+    fn letters(&mut self) -> Result<Rule, RuleError> {
+        let mut s = None;
+        let mut b = None;
+
+        while s.is_none() || b.is_none() {
+            let birth = match self.peek().map(|c| c.to_ascii_uppercase()) {
+                Some('B') if b.is_none() => true,
+                Some('S') if s.is_none() => false,
+                _ if b.is_none() => return error(self.position, Reason::Expected("'B'")),
+                _ => return error(self.position, Reason::Expected("'S'")),
+            };
+            self.position += 1;
+
+            let mask = Some(self.digits(birth)?);
+            if birth {
+                b = mask;
+            } else {
+                s = mask;
+            }
+
+            if s.is_none() || b.is_none() {
+                self.slash();
+            }
+        }
+
+        Ok(Rule::LifeLike {
+            s: s.unwrap_or(0),
+            b: b.unwrap_or(0),
+        })
+    }
+
+    // 23/3, survival first
+    fn numbers(&mut self) -> Result<Rule, RuleError> {
+        let s = self.digits(false)?;
+        if !self.slash() {
+            return error(self.position, Reason::Expected("'/'"));
+        }
+        let b = self.digits(true)?;
+
+        Ok(Rule::LifeLike { s, b })
+    }
+
+    // what comes after a life-like rule, which is only whitespace
+    fn suffix(&self, end: usize) -> Result<(), RuleError> {
+        let suffix: String = self.chars[self.position..end].iter().collect();
+        let family = match suffix.to_ascii_uppercase().as_str() {
+            "" => return Ok(()),
+            "H" => "hexagonal rules",
+            "V" => "von Neumann rules",
+            // B2/S/C3 or 2/2/3, with the number of states at the end
+            s if s.len() > 1
+                && (s.starts_with("/C")
+                    || s.starts_with('/') && s[1..].chars().all(|c| c.is_ascii_digit())) =>
+            {
+                "Generations rules"
+            }
+            _ => return error(self.position, Reason::UnknownSuffix(suffix)),
+        };
+        error(self.position, Reason::UnsupportedFamily(family))
+    }
+}
+
+impl Rule {
+    pub fn parse(text: &str) -> Result<Rule, RuleError> {
+        Ok(Self::parse_with(text, false)?.0)
+    }
+
+    // with wildcards an x after the digits of B or S stands for any other
+    // counts, whether it was given is returned for survival and birth
+    fn parse_with(text: &str, wildcards: bool) -> Result<(Rule, (bool, bool)), RuleError> {
+        let chars: Vec<char> = text.chars().collect();
+        let start = chars.iter().take_while(|c| c.is_whitespace()).count();
+        let end = chars.len() - chars.iter().rev().take_while(|c| c.is_whitespace()).count();
+
+        if start >= end {
+            return error(0, Reason::Empty);
+        }
+
+        let first = chars[start].to_ascii_uppercase();
+        let second = chars.get(start + 1).copied().unwrap_or(' ');
+
+        if first == 'R' && second.is_ascii_digit() {
+            let rule = Rule::LargerThanLife(LargerThanLife::parse(text)?);
+            return Ok((rule, (false, false)));
+        }
+
+        let mut parser = Parser {
+            chars,
+            position: start,
+            birth_zero: None,
+            wildcards,
+            any: (false, false),
+        };
+
+        let rule = match first {
+            'B' | 'S' => parser.letters()?,
+            '/' | '0'..='9' => parser.numbers()?,
+            'W' if second.is_ascii_digit() => {
+                return error(start, Reason::UnsupportedFamily("one-dimensional rules"));
+            }
+            '@' => {
+                return error(
+                    start,
+                    Reason::UnsupportedFamily("rule files in a rule string"),
+                );
+            }
+            _ => return error(start, Reason::Expected("a rule like B3/S23")),
+        };
+
+        parser.suffix(end)?;
+
+        if let Some(position) = parser.birth_zero {
+            return error(position, Reason::BirthWithoutNeighbours);
+        }
+        Ok((rule, parser.any))
+    }
+}
+
+// every count of neighbours for survival, and for birth without 0
+const ALL_S: usize = 0x1ff;
+const ALL_B: usize = 0x1fe;
+
+// the life-like rules that have at least the counts of the smallest rule and
+// at most those of the largest one
+pub struct RuleRange {
+    min_s: usize,
+    min_b: usize,
+    max_s: usize,
+    max_b: usize,
+}
+
+// a life-like rule at offset characters into a range
+fn life_like(text: &str, offset: usize) -> Result<(usize, usize), RuleError> {
+    let moved = |e: RuleError| RuleError {
+        position: e.position + offset,
+        reason: e.reason,
+    };
+
+    match Rule::parse(text).map_err(moved)? {
+        Rule::LifeLike { s, b } => Ok((s, b)),
+        Rule::LargerThanLife(_) => error(
+            offset,
+            Reason::UnsupportedFamily("larger than life rules in a rule range"),
+        ),
+    }
+}
+
+impl RuleRange {
+    // the smallest and largest rule like B3/S23..B36/S236, or a rule where
+    // an x adds any other counts like B3x/S2x, which is B3/S2..B12345678/S012345678
+    pub fn parse(text: &str) -> Result<RuleRange, RuleError> {
+        let start = text.chars().take_while(|c| c.is_whitespace()).count();
+        let mut rest = text.trim_start().chars();
+
+        if rest.next().is_some_and(|c| c.eq_ignore_ascii_case(&'R'))
+            && rest.next().is_some_and(|c| c.is_ascii_digit())
+        {
+            return error(
+                start,
+                Reason::UnsupportedFamily("larger than life rules in a rule range"),
+            );
+        }
+
+        if let Some((min, max)) = text.split_once("..") {
+            let offset = min.chars().count() + 2;
+            let (min_s, min_b) = life_like(min, 0)?;
+            let (max_s, max_b) = life_like(max, offset)?;
+
+            if min_s & !max_s != 0 || min_b & !max_b != 0 {
+                let message = format!("{} doesn't have all counts of {}", max.trim(), min.trim());
+                return error(offset, Reason::InvalidPart(message));
+            }
+            return Ok(RuleRange {
+                min_s,
+                min_b,
+                max_s,
+                max_b,
+            });
+        }
+
+        let (Rule::LifeLike { s, b }, (any_s, any_b)) = Rule::parse_with(text, true)? else {
+            unreachable!()
+        };
+
+        Ok(RuleRange {
+            min_s: s,
+            min_b: b,
+            max_s: if any_s { ALL_S } else { s },
+            max_b: if any_b { ALL_B } else { b },
+        })
+    }
+
+    // survival and birth counts of every rule in the range
+    pub fn rules(&self) -> impl Iterator<Item = (usize, usize)> {
+        // the counts that may or may not be there, birth above survival
+        let free = (self.max_s & !self.min_s) | (self.max_b & !self.min_b) << 9;
+        let (min_s, min_b) = (self.min_s, self.min_b);
+        let mut subset = Some(0);
+
+        std::iter::from_fn(move || {
+            let current: usize = subset?;
+            let next = current.wrapping_sub(free) & free;
+            subset = (next != 0).then_some(next);
+            Some((min_s | current & ALL_S, min_b | current >> 9))
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = |mask: usize| -> String {
+            (0..=8)
+                .filter(|n| mask >> n & 1 != 0)
+                .map(|n| char::from(b'0' + n as u8))
+                .collect()
+        };
+
+        match self {
+            Rule::LifeLike { s, b } => write!(f, "B{}/S{}", digits(*b), digits(*s)),
+            Rule::LargerThanLife(rule) => write!(f, "{}", rule.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn life_like(text: &str) -> (usize, usize) {
+        match Rule::parse(text) {
+            Ok(Rule::LifeLike { s, b }) => (s, b),
+            _ => panic!("{text} isn't a life-like rule"),
+        }
+    }
+
+    fn parse_error(text: &str) -> RuleError {
+        match Rule::parse(text) {
+            Ok(rule) => panic!("{text} parsed as {rule}"),
+            Err(error) => error,
+        }
+    }
+
+    fn range_error(text: &str) -> RuleError {
+        match RuleRange::parse(text) {
+            Ok(_) => panic!("{text} parsed as a range"),
+            Err(error) => error,
+        }
+    }
+
+    fn family(error: &RuleError) -> &'static str {
+        match error.reason {
+            Reason::UnsupportedFamily(family) => family,
+            _ => panic!("{error} is not about a family"),
+        }
+    }
+
+    const LIFE: (usize, usize) = (1 << 2 | 1 << 3, 1 << 3);
+
+    #[test]
+    fn orders() {
+        for text in ["B3/S23", "S23/B3", "b3s23", "s23b3", "23/3", "  B3/S23\n"] {
+            assert_eq!(life_like(text), LIFE, "{text}");
+        }
+        assert_eq!(life_like("B36/S23"), (LIFE.0, 1 << 3 | 1 << 6));
+        assert_eq!(life_like("/3"), (0, 1 << 3));
+        assert_eq!(Rule::parse("23/36").ok().unwrap().to_string(), "B36/S23");
+    }
+
+    #[test]
+    fn reasons() {
+        let error = parse_error(" \t");
+        assert!(matches!(error.reason, Reason::Empty));
+        assert_eq!(error.position, 0);
+
+        let error = parse_error("B33/S23");
+        assert!(matches!(error.reason, Reason::DuplicateDigit('3')));
+        assert_eq!(error.position, 2);
+
+        let error = parse_error("B3/S239");
+        assert!(matches!(error.reason, Reason::DigitTooLarge('9')));
+        assert_eq!(error.position, 6);
+
+        let error = parse_error("B3/S23?");
+        assert!(matches!(&error.reason, Reason::UnknownSuffix(s) if s == "?"));
+        assert_eq!(error.position, 6);
+
+        let error = parse_error("B3/S23H");
+        assert_eq!(family(&error), "hexagonal rules");
+        assert_eq!(error.position, 6);
+
+        let error = parse_error("B3/X23");
+        assert!(matches!(error.reason, Reason::Expected("'S'")));
+        assert_eq!(error.position, 3);
+
+        let error = parse_error("23");
+        assert!(matches!(error.reason, Reason::Expected("'/'")));
+        assert_eq!(error.position, 2);
+
+        let error = parse_error("B03/S23");
+        assert!(matches!(error.reason, Reason::BirthWithoutNeighbours));
+        assert_eq!(error.position, 1);
+
+        let error = parse_error("R5,C0,M1,S34..58,B34..45,NX");
+        assert!(matches!(error.reason, Reason::InvalidPart(_)));
+        assert_eq!(error.position, 25);
+    }
+
+    #[test]
+    fn positions_after_whitespace() {
+        let error = parse_error("   B33/S23");
+        assert_eq!(error.position, 5);
+        assert_eq!(error.to_string(), "digit 3 is listed twice at character 6");
+
+        let error = parse_error("  23/39");
+        assert_eq!(error.position, 6);
+
+        let error = parse_error("  R5, C0,M1,S34..58,B0..45,NM");
+        assert!(matches!(error.reason, Reason::BirthWithoutNeighbours));
+        assert_eq!(error.position, 20);
+
+        let error = range_error("B3/S23..  B3/S239");
+        assert!(matches!(error.reason, Reason::DigitTooLarge('9')));
+        assert_eq!(error.position, 16);
+    }
+
+    #[test]
+    fn families() {
+        for (text, expected, position) in [
+            ("B2/S34H", "hexagonal rules", 6),
+            ("B2/S3V", "von Neumann rules", 5),
+            ("B2/S/C3", "Generations rules", 4),
+            ("23/3/3", "Generations rules", 4),
+            ("B2n3/S23", "isotropic non-totalistic rules", 2),
+            ("W30", "one-dimensional rules", 0),
+            ("@RULE Life", "rule files in a rule string", 0),
+        ] {
+            let error = parse_error(text);
+            assert_eq!(family(&error), expected, "{text}");
+            assert_eq!(error.position, position, "{text}");
+        }
+
+        let error = range_error("R5,C0,M1,S34..58,B34..45,NM");
+        assert_eq!(family(&error), "larger than life rules in a rule range");
+    }
+
+    #[test]
+    fn range_counts() {
+        let count = |text: &str| RuleRange::parse(text).ok().unwrap().rules().count();
+
+        // 8 survival and 7 birth counts are free
+        assert_eq!(count("B3x/S2x"), 1 << 15);
+        assert_eq!(count("B3/S23x"), 1 << 7);
+        assert_eq!(count("B3/S23"), 1);
+        assert_eq!(count("B3/S23..B36/S236"), 4);
+
+        let rules: Vec<_> = RuleRange::parse("B3/S23..B36/S23")
+            .ok()
+            .unwrap()
+            .rules()
+            .collect();
+        assert_eq!(rules, [LIFE, (LIFE.0, 1 << 3 | 1 << 6)]);
+
+        let error = range_error("B36/S23..B3/S23");
+        assert!(matches!(error.reason, Reason::InvalidPart(_)));
+        assert_eq!(error.position, 9);
+    }
+}
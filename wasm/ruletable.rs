@@ -0,0 +1,824 @@
+// Rules of Golly's rule files (.rule), with a @TABLE or a @TREE, for up to 256
+// states on the Moore or the von Neumann neighbourhood. The other sections,
+// like @COLORS or @ICONS, are skipped.
+//
+// State 0 has to stay 0 without living neighbours, like the empty space of
+// life-like rules, so that hashlife can treat it as the background.
+
+use rustc_hash::FxBuildHasher;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const MAX_STATES: usize = 256;
+
+// a cell and its neighbours, in the order of the transitions of a @TABLE:
+// the cell, then clockwise starting at the top
+pub type Neighbourhood = [u8; 9];
+
+const CENTRE: usize = 0;
+const N: usize = 1;
+const NE: usize = 2;
+const E: usize = 3;
+const SE: usize = 4;
+const S: usize = 5;
+const SW: usize = 6;
+const W: usize = 7;
+const NW: usize = 8;
+
+// the order in which a @TREE looks at the cells
+const TREE_MOORE: [usize; 9] = [NW, NE, SW, SE, N, W, E, S, CENTRE];
+const TREE_VON_NEUMANN: [usize; 5] = [N, W, E, S, CENTRE];
+
+// the neighbourhood positions of the inputs of a @TABLE transition
+const TABLE_MOORE: [usize; 9] = [CENTRE, N, NE, E, SE, S, SW, W, NW];
+const TABLE_VON_NEUMANN: [usize; 5] = [CENTRE, N, E, S, W];
+
+// ids start at 1, 0 stands for the life-like rules
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+// an id for a new rule, also used by the other rule families
+pub fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub struct RuleTableError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RuleTableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, RuleTableError> {
+    Err(RuleTableError {
+        line,
+        message: message.into(),
+    })
+}
+
+// one bit per state
+#[derive(Clone, Copy, PartialEq)]
+struct StateSet([u64; 4]);
+
+impl StateSet {
+    fn single(state: usize) -> StateSet {
+        let mut set = StateSet([0; 4]);
+        set.0[state / 64] |= 1 << (state % 64);
+        set
+    }
+
+    fn union(self, other: StateSet) -> StateSet {
+        StateSet(std::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+
+    fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] >> (state % 64) & 1 != 0
+    }
+}
+
+enum Output {
+    State(u8),
+    // the state of an input, for outputs that are variables
+    Input(usize),
+}
+
+struct Transition {
+    inputs: Vec<StateSet>,
+    // inputs with the same variable must have the same state
+    bound: Vec<(usize, usize)>,
+    output: Output,
+}
+
+struct Table {
+    positions: &'static [usize],
+    // every way to map the inputs of a transition to the neighbourhood, as
+    // indices into positions. None stands for any order of the neighbours.
+    symmetries: Option<Vec<Vec<usize>>>,
+    transitions: Vec<Transition>,
+}
+
+struct Tree {
+    order: &'static [usize],
+    states: usize,
+    // states entries per node, the children of higher nodes or the results of
+    // the lowest ones
+    nodes: Vec<u32>,
+    root: usize,
+}
+
+enum Rule {
+    Table(Table),
+    Tree(Tree),
+}
+
+pub struct RuleTable {
+    pub name: String,
+    pub states: usize,
+    // identifies the rule among the cached results of the node store
+    pub id: usize,
+    // the rule file, so that it can be saved with the pattern
+    #[cfg(not(target_arch = "wasm32"))]
+    pub source: String,
+    rule: Rule,
+    results: RefCell<HashMap<Neighbourhood, u8, FxBuildHasher>>,
+}
+
+impl Table {
+    fn matches(&self, transition: &Transition, cells: &Neighbourhood, order: &[usize]) -> bool {
+        let cell = |input: usize| cells[self.positions[order[input]]];
+
+        transition
+            .inputs
+            .iter()
+            .enumerate()
+            .all(|(input, set)| set.contains(cell(input)))
+            && transition.bound.iter().all(|&(a, b)| cell(a) == cell(b))
+    }
+
+    // any order of the neighbours, tried one input after the other
+    fn matches_permuted(
+        &self,
+        transition: &Transition,
+        cells: &Neighbourhood,
+        order: &mut Vec<usize>,
+    ) -> bool {
+        if order.len() == self.positions.len() {
+            return self.matches(transition, cells, order);
+        }
+
+        let input = order.len();
+        for position in 1..self.positions.len() {
+            if order.contains(&position)
+                || !transition.inputs[input].contains(cells[self.positions[position]])
+            {
+                continue;
+            }
+
+            order.push(position);
+            let found = self.matches_permuted(transition, cells, order);
+            order.pop();
+
+            if found {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn next(&self, cells: &Neighbourhood) -> u8 {
+        for transition in &self.transitions {
+            let order = match &self.symmetries {
+                Some(symmetries) => symmetries
+                    .iter()
+                    .find(|order| self.matches(transition, cells, order)),
+                None => {
+                    let mut order = vec![CENTRE];
+                    if !transition.inputs[CENTRE].contains(cells[CENTRE])
+                        || !self.matches_permuted(transition, cells, &mut order)
+                    {
+                        continue;
+                    }
+                    return self.output(transition, cells, &order);
+                }
+            };
+
+            if let Some(order) = order {
+                return self.output(transition, cells, order);
+            }
+        }
+
+        // no transition, the cell stays as it is
+        cells[CENTRE]
+    }
+
+    fn output(&self, transition: &Transition, cells: &Neighbourhood, order: &[usize]) -> u8 {
+        match transition.output {
+            Output::State(state) => state,
+            Output::Input(input) => cells[self.positions[order[input]]],
+        }
+    }
+}
+
+impl Tree {
+    fn next(&self, cells: &Neighbourhood) -> u8 {
+        let mut node = self.root;
+        for &position in self.order {
+            // states the rule doesn't know are taken for the background
+            let state = cells[position] as usize;
+            let state = if state < self.states { state } else { 0 };
+            node = self.nodes[node * self.states + state] as usize;
+        }
+        node as u8
+    }
+}
+
+// the orders of the inputs of a transition that count as the same
+fn symmetries(name: &str, ring: usize) -> Option<Vec<Vec<usize>>> {
+    let rotations: &[usize] = match (name, ring) {
+        ("none" | "reflect_horizontal", _) => &[0],
+        ("rotate2", 8) => &[0, 4],
+        ("rotate2", 4) => &[0, 2],
+        ("rotate4" | "rotate4reflect", 8) => &[0, 2, 4, 6],
+        ("rotate4" | "rotate4reflect", 4) => &[0, 1, 2, 3],
+        ("rotate8" | "rotate8reflect", 8) => &[0, 1, 2, 3, 4, 5, 6, 7],
+        _ => return None,
+    };
+    let reflect = name.ends_with("reflect") || name == "reflect_horizontal";
+
+    let mut orders = vec![];
+    for &rotation in rotations {
+        for mirrored in [false, true] {
+            if mirrored && !reflect {
+                continue;
+            }
+
+            let mut order = vec![CENTRE];
+            for i in 0..ring {
+                let i = if mirrored { (ring - i) % ring } else { i };
+                order.push((i + rotation) % ring + 1);
+            }
+            orders.push(order);
+        }
+    }
+    Some(orders)
+}
+
+// the lines of a section without comments, with their line numbers
+fn section<'a>(source: &'a str, name: &str) -> Option<Vec<(usize, &'a str)>> {
+    let mut lines = source.lines().enumerate();
+    lines.find(|(_, line)| line.trim().starts_with(name))?;
+
+    Some(
+        lines
+            .take_while(|(_, line)| !line.trim_start().starts_with('@'))
+            .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect(),
+    )
+}
+
+// the value of a "key:value" or "key=value" line
+fn setting<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(key)?.trim_start();
+    let value = rest.strip_prefix(':').or_else(|| rest.strip_prefix('='))?;
+    Some(value.trim())
+}
+
+fn parse_state(line: usize, token: &str, states: usize) -> Result<usize, RuleTableError> {
+    match token.parse::<usize>() {
+        Ok(state) if state < states => Ok(state),
+        Ok(state) => error(
+            line,
+            format!("state {state} out of range, there are {states} states"),
+        ),
+        Err(_) => error(line, format!("unknown state or variable '{token}'")),
+    }
+}
+
+// the states of a list like {0,1,a} or of a single entry, variables stand
+// for their states
+fn parse_set(
+    line: usize,
+    list: &str,
+    states: usize,
+    variables: &HashMap<String, StateSet>,
+) -> Result<StateSet, RuleTableError> {
+    let list = match list.strip_prefix('{') {
+        Some(inner) => match inner.strip_suffix('}') {
+            Some(inner) => inner,
+            None => return error(line, format!("unclosed list '{list}'")),
+        },
+        None => list,
+    };
+
+    let mut set = StateSet([0; 4]);
+    for token in list.split(',').map(str::trim) {
+        set = set.union(match variables.get(token) {
+            Some(&variable) => variable,
+            None => StateSet::single(parse_state(line, token, states)?),
+        });
+    }
+    Ok(set)
+}
+
+// the entries of a transition, separated by commas outside of braces or, if
+// there are none, one character each
+fn tokens(line: &str) -> Vec<String> {
+    if !line.contains(',') {
+        return line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from)
+            .collect();
+    }
+
+    let mut tokens = vec![String::new()];
+    let mut depth = 0;
+
+    for c in line.chars() {
+        match c {
+            ',' if depth == 0 => tokens.push(String::new()),
+            _ => {
+                depth += (c == '{') as i32 - (c == '}') as i32;
+                if !c.is_whitespace() {
+                    tokens.last_mut().expect("one token at least").push(c);
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_table(lines: &[(usize, &str)]) -> Result<(Table, usize), RuleTableError> {
+    let mut states = 0;
+    let mut positions: &'static [usize] = &TABLE_MOORE;
+    let mut symmetry_name = "none";
+    let mut variables: HashMap<String, StateSet> = HashMap::new();
+    let mut transitions = vec![];
+
+    for &(line, text) in lines {
+        if let Some(value) = setting(text, "n_states") {
+            states = match value.parse() {
+                Ok(n @ 2..=MAX_STATES) => n,
+                _ => return error(line, format!("n_states must be between 2 and {MAX_STATES}")),
+            };
+        } else if let Some(value) = setting(text, "neighborhood") {
+            positions = match value {
+                "Moore" => &TABLE_MOORE,
+                "vonNeumann" => &TABLE_VON_NEUMANN,
+                _ => return error(line, format!("unsupported neighborhood '{value}'")),
+            };
+        } else if let Some(value) = setting(text, "symmetries") {
+            symmetry_name = value;
+            if value != "permute" && symmetries(value, positions.len() - 1).is_none() {
+                return error(line, format!("unsupported symmetries '{value}'"));
+            }
+        } else if states == 0 {
+            return error(line, "n_states has to come first");
+        } else if let Some(declaration) = text.strip_prefix("var ") {
+            let Some((name, list)) = declaration.split_once('=') else {
+                return error(line, "expected var name={...}");
+            };
+            let set = parse_set(line, list.trim(), states, &variables)?;
+            variables.insert(name.trim().to_string(), set);
+        } else {
+            transitions.push(parse_transition(
+                line,
+                text,
+                states,
+                positions.len(),
+                &variables,
+            )?);
+        }
+    }
+
+    if states == 0 {
+        return error(lines.first().map_or(0, |l| l.0), "n_states is missing");
+    }
+
+    let table = Table {
+        positions,
+        symmetries: symmetries(symmetry_name, positions.len() - 1),
+        transitions,
+    };
+    Ok((table, states))
+}
+
+fn parse_transition(
+    line: usize,
+    text: &str,
+    states: usize,
+    inputs: usize,
+    variables: &HashMap<String, StateSet>,
+) -> Result<Transition, RuleTableError> {
+    let tokens = tokens(text);
+    if tokens.len() != inputs + 1 {
+        return error(
+            line,
+            format!("expected {} entries, got {}", inputs + 1, tokens.len()),
+        );
+    }
+
+    let mut transition = Transition {
+        inputs: vec![],
+        bound: vec![],
+        output: Output::State(0),
+    };
+    let mut first_use: HashMap<&str, usize> = HashMap::new();
+
+    for (input, token) in tokens[..inputs].iter().enumerate() {
+        let set = if token.starts_with('{') {
+            parse_set(line, token, states, variables)?
+        } else if let Some(&set) = variables.get(token.as_str()) {
+            // a variable stands for the same state wherever it is used
+            match first_use.get(token.as_str()) {
+                Some(&first) => transition.bound.push((first, input)),
+                None => {
+                    first_use.insert(token, input);
+                }
+            }
+            set
+        } else {
+            StateSet::single(parse_state(line, token, states)?)
+        };
+        transition.inputs.push(set);
+    }
+
+    let output = &tokens[inputs];
+    transition.output = match first_use.get(output.as_str()) {
+        Some(&input) => Output::Input(input),
+        None if variables.contains_key(output) => {
+            return error(line, format!("output variable '{output}' isn't an input"));
+        }
+        None => Output::State(parse_state(line, output, states)? as u8),
+    };
+
+    Ok(transition)
+}
+
+fn parse_tree(lines: &[(usize, &str)]) -> Result<(Tree, usize), RuleTableError> {
+    let mut states = 0;
+    let mut order: &'static [usize] = &TREE_MOORE;
+    let mut count = 0;
+    // the level of every node, to check their children
+    let mut levels = vec![];
+    let mut nodes = vec![];
+
+    for &(line, text) in lines {
+        if let Some(value) = setting(text, "num_states") {
+            states = match value.parse() {
+                Ok(n @ 2..=MAX_STATES) => n,
+                _ => {
+                    return error(
+                        line,
+                        format!("num_states must be between 2 and {MAX_STATES}"),
+                    );
+                }
+            };
+        } else if let Some(value) = setting(text, "num_neighbors") {
+            order = match value {
+                "8" => &TREE_MOORE,
+                "4" => &TREE_VON_NEUMANN,
+                _ => return error(line, format!("unsupported num_neighbors '{value}'")),
+            };
+        } else if let Some(value) = setting(text, "num_nodes") {
+            count = value
+                .parse()
+                .or_else(|_| error(line, format!("invalid num_nodes '{value}'")))?;
+        } else if states == 0 {
+            return error(line, "num_states has to come first");
+        } else {
+            let numbers: Result<Vec<usize>, _> = text.split_whitespace().map(str::parse).collect();
+            let Ok(numbers) = numbers else {
+                return error(line, "expected a node");
+            };
+            let Some((&level, entries)) = numbers.split_first() else {
+                return error(line, "expected a node");
+            };
+            if entries.len() != states || level == 0 || level > order.len() {
+                return error(
+                    line,
+                    format!(
+                        "expected a level up to {} and {states} entries",
+                        order.len()
+                    ),
+                );
+            }
+
+            for &entry in entries {
+                let valid = if level == 1 {
+                    entry < states
+                } else {
+                    levels.get(entry) == Some(&(level - 1))
+                };
+                if !valid {
+                    return error(
+                        line,
+                        format!("invalid entry {entry} of a level {level} node"),
+                    );
+                }
+                nodes.push(entry as u32);
+            }
+            levels.push(level);
+        }
+    }
+
+    let last = lines.last().map_or(0, |l| l.0);
+    if levels.len() != count || count == 0 {
+        return error(
+            last,
+            format!("expected {count} nodes, got {}", levels.len()),
+        );
+    }
+    if levels[count - 1] != order.len() {
+        return error(last, "the last node has to be the root");
+    }
+
+    let tree = Tree {
+        order,
+        states,
+        nodes,
+        root: count - 1,
+    };
+    Ok((tree, states))
+}
+
+impl RuleTable {
+    pub fn parse(source: &str) -> Result<RuleTable, RuleTableError> {
+        let name = source
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("@RULE"))
+            .map_or(String::new(), |name| name.trim().to_string());
+
+        let table = source.lines().position(|l| l.trim().starts_with("@TABLE"));
+        let tree = source.lines().position(|l| l.trim().starts_with("@TREE"));
+
+        // the first of them if there are both
+        let (rule, states) = match (table, tree) {
+            (Some(table), Some(tree)) if tree < table => {
+                let (tree, states) = parse_tree(&section(source, "@TREE").unwrap_or_default())?;
+                (Rule::Tree(tree), states)
+            }
+            (Some(_), _) => {
+                let (table, states) = parse_table(&section(source, "@TABLE").unwrap_or_default())?;
+                (Rule::Table(table), states)
+            }
+            (None, Some(_)) => {
+                let (tree, states) = parse_tree(&section(source, "@TREE").unwrap_or_default())?;
+                (Rule::Tree(tree), states)
+            }
+            (None, None) => return error(0, "no @TABLE or @TREE"),
+        };
+
+        let table = RuleTable {
+            name,
+            states,
+            id: next_id(),
+            #[cfg(not(target_arch = "wasm32"))]
+            source: source.to_string(),
+            rule,
+            results: RefCell::new(HashMap::default()),
+        };
+
+        if table.next([0; 9]) != 0 {
+            return error(0, "state 0 has to stay 0 without neighbours");
+        }
+        Ok(table)
+    }
+
+    // the next state of the cell in the centre
+    pub fn next(&self, mut cells: Neighbourhood) -> u8 {
+        let positions = match &self.rule {
+            Rule::Table(table) => table.positions,
+            Rule::Tree(tree) => tree.order,
+        };
+        if positions.len() == 5 {
+            // the corners don't count, they shouldn't take up room in the results
+            for corner in [NE, SE, SW, NW] {
+                cells[corner] = 0;
+            }
+        }
+
+        if let Some(&next) = self.results.borrow().get(&cells) {
+            return next;
+        }
+
+        let next = match &self.rule {
+            Rule::Table(table) => table.next(&cells),
+            Rule::Tree(tree) => tree.next(&cells),
+        };
+        self.results.borrow_mut().insert(cells, next);
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LifeUniverse;
+    use crate::tests::{cells_of, load, soup};
+
+    const LIFE_TABLE: &str = "@RULE LifeTable
+@TABLE
+n_states:2
+neighborhood:Moore
+symmetries:permute
+var a={0,1}
+var b=a
+var c=a
+var d=a
+var e=a
+var f=a
+var g=a
+var h=a
+0,1,1,1,0,0,0,0,0,1
+1,1,1,0,0,0,0,0,0,1
+1,1,1,1,0,0,0,0,0,1
+1,a,b,c,d,e,f,g,h,0
+";
+
+    // a @TREE of the rule given by next of the cells in the order of TREE_MOORE
+    fn tree(name: &str, next: impl Fn(&[usize]) -> usize) -> String {
+        fn node(
+            cells: &mut Vec<usize>,
+            next: &dyn Fn(&[usize]) -> usize,
+            nodes: &mut Vec<String>,
+        ) -> usize {
+            if cells.len() == TREE_MOORE.len() {
+                return next(cells);
+            }
+
+            let mut line = (TREE_MOORE.len() - cells.len()).to_string();
+            for state in 0..2 {
+                cells.push(state);
+                line += &format!(" {}", node(cells, next, nodes));
+                cells.pop();
+            }
+
+            nodes.iter().position(|n| *n == line).unwrap_or_else(|| {
+                nodes.push(line);
+                nodes.len() - 1
+            })
+        }
+
+        let mut nodes = vec![];
+        node(&mut vec![], &next, &mut nodes);
+        format!(
+            "@RULE {name}\n@TREE\nnum_states=2\nnum_neighbors=8\nnum_nodes={}\n{}\n",
+            nodes.len(),
+            nodes.join("\n")
+        )
+    }
+
+    // the cell with the neighbours of the bits of mask, bit 0 for N and on clockwise
+    fn cells(centre: u8, mask: usize) -> Neighbourhood {
+        let mut cells = [0; 9];
+        cells[CENTRE] = centre;
+        for (i, position) in [N, NE, E, SE, S, SW, W, NW].into_iter().enumerate() {
+            cells[position] = (mask >> i & 1) as u8;
+        }
+        cells
+    }
+
+    fn matches_life(table: &RuleTable) {
+        for centre in 0..2 {
+            for mask in 0..256usize {
+                let count = mask.count_ones();
+                let alive = count == 3 || centre == 1 && count == 2;
+                assert_eq!(
+                    table.next(cells(centre, mask)),
+                    alive as u8,
+                    "{centre} {mask:08b}"
+                );
+            }
+        }
+
+        for seed in 1..4 {
+            let mut life = LifeUniverse::new();
+            let mut custom = LifeUniverse::new();
+            custom.set_rule_table(&table.source).ok().unwrap();
+
+            for universe in [&mut life, &mut custom] {
+                load(universe, &soup(seed, 32, 32));
+            }
+            for step in [0, 0, 0, 1, 3] {
+                for universe in [&mut life, &mut custom] {
+                    universe.set_step(step);
+                    universe.next_generation(true);
+                }
+                assert_eq!(
+                    cells_of(&custom),
+                    cells_of(&life),
+                    "seed {seed} step {step}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn life_table() {
+        let table = RuleTable::parse(LIFE_TABLE).ok().unwrap();
+        assert_eq!(table.name, "LifeTable");
+        assert_eq!(table.states, 2);
+        matches_life(&table);
+    }
+
+    #[test]
+    fn life_tree() {
+        let source = tree("LifeTree", |cells| {
+            let count: usize = cells[..8].iter().sum();
+            (count == 3 || cells[8] == 1 && count == 2) as usize
+        });
+        let table = RuleTable::parse(&source).ok().unwrap();
+        assert_eq!(table.name, "LifeTree");
+        matches_life(&table);
+    }
+
+    // the neighbourhoods with a dead centre that come alive, for a table with
+    // one birth on N and NE
+    fn births(symmetries: &str) -> Vec<usize> {
+        let source = format!(
+            "@TABLE\nn_states:2\nneighborhood:Moore\nsymmetries:{symmetries}\n0,1,1,0,0,0,0,0,0,1\n"
+        );
+        let table = RuleTable::parse(&source).ok().unwrap();
+        (0..256)
+            .filter(|&mask| table.next(cells(0, mask)) == 1)
+            .collect()
+    }
+
+    // neighbour masks, bit 0 for N and on clockwise
+    const NORTH: usize = 1;
+    const NORTH_EAST: usize = 2;
+    const SOUTH: usize = 16;
+    const SOUTH_WEST: usize = 32;
+    const WEST: usize = 64;
+    const NORTH_WEST: usize = 128;
+
+    #[test]
+    fn no_symmetries() {
+        assert_eq!(births("none"), [NORTH | NORTH_EAST]);
+    }
+
+    #[test]
+    fn reflect_horizontal() {
+        assert_eq!(
+            births("reflect_horizontal"),
+            [NORTH | NORTH_EAST, NORTH | NORTH_WEST]
+        );
+    }
+
+    #[test]
+    fn rotate2() {
+        assert_eq!(births("rotate2"), [NORTH | NORTH_EAST, SOUTH | SOUTH_WEST]);
+    }
+
+    #[test]
+    fn rotate4() {
+        let births = births("rotate4");
+        assert_eq!(births.len(), 4);
+        // turned a quarter to the left
+        assert!(births.contains(&(WEST | NORTH_WEST)));
+        assert!(!births.contains(&(NORTH | NORTH_WEST)));
+    }
+
+    #[test]
+    fn rotate4reflect() {
+        let births = births("rotate4reflect");
+        assert_eq!(births.len(), 8);
+        assert!(births.contains(&(WEST | NORTH_WEST)));
+        assert!(births.contains(&(NORTH | NORTH_WEST)));
+    }
+
+    #[test]
+    fn rotate8() {
+        let births = births("rotate8");
+        // every pair of neighbours next to each other
+        assert_eq!(births.len(), 8);
+        assert!(births.contains(&(NORTH | NORTH_WEST)));
+        assert!(births.contains(&(SOUTH | SOUTH_WEST)));
+    }
+
+    #[test]
+    fn rotate8reflect() {
+        assert_eq!(births("rotate8reflect"), births("rotate8"));
+    }
+
+    #[test]
+    fn permute() {
+        let births = births("permute");
+        // any two of the eight neighbours
+        assert_eq!(births.len(), 28);
+        assert!(births.iter().all(|mask| mask.count_ones() == 2));
+    }
+
+    #[test]
+    fn von_neumann_rotate4() {
+        let source =
+            "@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate4\n0,1,0,0,0,1\n";
+        let table = RuleTable::parse(source).ok().unwrap();
+        let births: Vec<usize> = (0..256)
+            .filter(|&mask| table.next(cells(0, mask)) == 1)
+            .collect();
+
+        // one of N, E, S and W with any corners, which are ignored
+        assert_eq!(births.len(), 4 * 16);
+        assert!(
+            births
+                .iter()
+                .all(|mask| (mask & 0b0101_0101).count_ones() == 1)
+        );
+    }
+
+    #[test]
+    fn unknown_symmetries() {
+        let source = "@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate8\n";
+        let error = RuleTable::parse(source).err().unwrap();
+        assert_eq!(error.line, 4);
+    }
+}
@@ -0,0 +1,414 @@
+// Saves and loads a universe with its node store, native targets only. Long
+// runs can be stopped and resumed later, optionally with the cached results so
+// that they don't have to be computed again.
+//
+// The file holds the nodes of the pattern, or of the whole store with caches,
+// lower levels first, so children and cached results always come before the
+// nodes that use them.
+// The first indices are the cells, one for every state, so nodes start at
+// MAX_STATES. All numbers are little endian.
+
+use super::{
+    CustomRule, FIRST_NODE, FREE, HistoryEntry, LEVEL1_NODES, LargerThanLife, LifeUniverse,
+    MAX_STATES, NodeId, NodeMap, PatternInfo, RuleTable,
+};
+use rustc_hash::FxBuildHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::num::NonZeroU32;
+use std::rc::Rc;
+
+const MAGIC: &[u8; 8] = b"LIFESTAT";
+const VERSION: u32 = 3;
+// stands for a missing cached result
+const NONE: u32 = u32::MAX;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// strings are utf-8, prefixed with their length in bytes
+fn write_strings(out: &mut impl Write, strings: &[String]) -> io::Result<()> {
+    out.write_all(&(strings.len() as u32).to_le_bytes())?;
+    for string in strings {
+        out.write_all(&(string.len() as u32).to_le_bytes())?;
+        out.write_all(string.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_strings(reader: &mut impl Read) -> io::Result<Vec<String>> {
+    let count = read_u32(reader)?;
+    let mut strings = vec![];
+
+    for _ in 0..count {
+        let mut bytes = vec![];
+        let length = read_u32(reader)? as u64;
+        reader.take(length).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        strings.push(String::from_utf8(bytes).map_err(|_| invalid("text isn't utf-8"))?);
+    }
+
+    Ok(strings)
+}
+
+impl NodeMap {
+    fn live_ids(&self) -> impl Iterator<Item = NodeId> {
+        (FIRST_NODE as u32..self.nodes.len() as u32)
+            .filter_map(NonZeroU32::new)
+            .map(NodeId)
+            .filter(|&id| self[id].level != FREE)
+    }
+
+    fn fixed_ids(&self) -> impl Iterator<Item = NodeId> {
+        (LEVEL1_NODES as u32..FIRST_NODE as u32)
+            .filter_map(NonZeroU32::new)
+            .map(NodeId)
+    }
+}
+
+impl CustomRule {
+    // a rule file or a larger than life rule, as written by source
+    fn parse(source: &str) -> Result<CustomRule, String> {
+        if source.contains('@') {
+            let table = RuleTable::parse(source).map_err(|error| error.to_string())?;
+            Ok(CustomRule::Table(Rc::new(table)))
+        } else {
+            let rule = LargerThanLife::parse(source).map_err(|error| error.to_string())?;
+            Ok(CustomRule::LargerThanLife(Rc::new(rule)))
+        }
+    }
+
+    fn source(&self) -> String {
+        match self {
+            CustomRule::Table(table) => table.source.clone(),
+            CustomRule::LargerThanLife(rule) => rule.name(),
+        }
+    }
+}
+
+impl LifeUniverse {
+    // the nodes to save, by level. With caches that is the whole store, as
+    // most of the cached results belong to nodes that aren't part of the
+    // current generation. They can only be saved if they were computed for
+    // the current rule and step.
+    fn saved_nodes(&self, root: NodeId, with_caches: bool) -> Vec<NodeId> {
+        let hashmap = self.hashmap.borrow();
+
+        let mut nodes = if with_caches {
+            let mut nodes: Vec<NodeId> = hashmap.live_ids().collect();
+
+            // the fixed nodes aren't in the store, the ones that are used or
+            // have results of their own are saved like the others
+            let fixed = |id: &NodeId| (LEVEL1_NODES..FIRST_NODE).contains(&id.index());
+            let mut stack: Vec<NodeId> = hashmap
+                .fixed_ids()
+                .filter(|&id| hashmap[id].cache.is_some() || hashmap[id].quick_cache.is_some())
+                .collect();
+            for &id in &nodes {
+                let node = &hashmap[id];
+                stack.extend(
+                    node.children()
+                        .iter()
+                        .chain(&node.cache)
+                        .chain(&node.quick_cache),
+                );
+            }
+
+            let mut seen = HashSet::<NodeId, FxBuildHasher>::default();
+            while let Some(id) = stack.pop() {
+                if fixed(&id) && seen.insert(id) {
+                    nodes.push(id);
+                    let node = &hashmap[id];
+                    stack.extend(
+                        node.children()
+                            .iter()
+                            .chain(&node.cache)
+                            .chain(&node.quick_cache),
+                    );
+                }
+            }
+            nodes
+        } else {
+            let mut seen = HashSet::<NodeId, FxBuildHasher>::default();
+            let mut nodes = vec![];
+            let mut stack = vec![root];
+
+            while let Some(id) = stack.pop() {
+                if hashmap[id].level == 0 || !seen.insert(id) {
+                    continue;
+                }
+                nodes.push(id);
+                stack.extend(hashmap[id].children());
+            }
+            nodes
+        };
+
+        nodes.sort_by_key(|&id| hashmap[id].level);
+        nodes
+    }
+
+    // writes the pattern, the rule, the step, the generation and the pattern
+    // info to a file, with_caches also writes the cached results. A custom
+    // rule is saved as the text it was made from.
+    #[allow(dead_code)]
+    pub fn save_state(&self, path: &str, with_caches: bool) -> io::Result<()> {
+        let root = self.root();
+        let with_caches = with_caches && self.hashmap.borrow().cached_for == Some(self.cache_key());
+        let nodes = self.saved_nodes(root, with_caches);
+
+        let mut indices = HashMap::<NodeId, u32, FxBuildHasher>::default();
+        for state in 0..MAX_STATES {
+            indices.insert(NodeId::leaf(state as u8), state as u32);
+        }
+        for (i, &id) in nodes.iter().enumerate() {
+            indices.insert(id, (i + MAX_STATES) as u32);
+        }
+
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.rule_s as u32).to_le_bytes())?;
+        out.write_all(&(self.rule_b as u32).to_le_bytes())?;
+        // no string without a custom rule
+        let custom_rule: Vec<String> = self.custom_rule.iter().map(CustomRule::source).collect();
+        write_strings(&mut out, &custom_rule)?;
+        out.write_all(&(self.step as u32).to_le_bytes())?;
+        out.write_all(&self.generation.to_le_bytes())?;
+        out.write_all(&[with_caches as u8])?;
+        out.write_all(&(nodes.len() as u64).to_le_bytes())?;
+
+        let hashmap = self.hashmap.borrow();
+        for &id in &nodes {
+            let node = &hashmap[id];
+            for child in node.children() {
+                out.write_all(&indices[&child].to_le_bytes())?;
+            }
+
+            if with_caches {
+                for cache in [node.cache, node.quick_cache] {
+                    let index = cache.map_or(NONE, |n| indices[&n]);
+                    out.write_all(&index.to_le_bytes())?;
+                }
+            }
+        }
+
+        out.write_all(&indices[&root].to_le_bytes())?;
+
+        let info = &self.info;
+        write_strings(
+            &mut out,
+            &[
+                info.name.clone(),
+                info.author.clone(),
+                info.source_url.clone(),
+            ],
+        )?;
+        write_strings(&mut out, &info.comments)?;
+        write_strings(&mut out, &info.urls)?;
+        out.flush()
+    }
+
+    // replaces the pattern, the rule, the step, the generation and the pattern
+    // info with the ones saved by save_state. The universe is unchanged if the
+    // file can't be read.
+    #[allow(dead_code)]
+    pub fn load_state(&mut self, path: &str) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(&mut reader)? != VERSION {
+            return Err(invalid("not a saved universe"));
+        }
+
+        let rule_s = read_u32(&mut reader)? as usize;
+        let rule_b = read_u32(&mut reader)? as usize;
+        let custom_rule = match read_strings(&mut reader)?.first() {
+            Some(source) => Some(
+                CustomRule::parse(source)
+                    .map_err(|error| invalid(&format!("custom rule {error}")))?,
+            ),
+            None => None,
+        };
+        let step = read_u32(&mut reader)? as usize;
+        let generation = f64::from_bits(read_u64(&mut reader)?);
+        let mut with_caches = [0];
+        reader.read_exact(&mut with_caches)?;
+        let with_caches = with_caches[0] != 0;
+        let count = read_u64(&mut reader)? as usize;
+
+        // the caches of the store are made to match the saved ones first
+        let saved = self.history_entry();
+        self.set_rules(rule_s, rule_b);
+        self.set_custom_rule(custom_rule.clone());
+        self.set_step(step);
+        self.use_caches();
+
+        let mut built: Vec<NodeId> = (0..MAX_STATES)
+            .map(|state| NodeId::leaf(state as u8))
+            .collect();
+        let result = self.load_nodes(&mut reader, count, with_caches, &mut built);
+
+        let root = result.and_then(|()| {
+            let index = read_u32(&mut reader)? as usize;
+            built
+                .get(index)
+                .copied()
+                .filter(|&root| self.node(root).level >= 3)
+                .ok_or_else(|| invalid("root out of range"))
+        });
+
+        let info = root.and_then(|root| {
+            let [name, author, source_url] = read_strings(&mut reader)?
+                .try_into()
+                .map_err(|_| invalid("pattern info is incomplete"))?;
+            let info = PatternInfo {
+                name,
+                author,
+                comments: read_strings(&mut reader)?,
+                urls: read_strings(&mut reader)?,
+                source_url,
+            };
+            Ok((root, info))
+        });
+
+        let (root, info) = match info {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                self.restore_history_entry(&saved);
+                return Err(error);
+            }
+        };
+
+        self.restore_history_entry(&HistoryEntry {
+            root,
+            generation,
+            rule_s,
+            rule_b,
+            custom_rule,
+            step,
+        });
+        self.info = info;
+
+        if self.cell_history.is_some() {
+            self.start_cell_history();
+        }
+        Ok(())
+    }
+
+    fn load_nodes(
+        &mut self,
+        reader: &mut impl Read,
+        count: usize,
+        with_caches: bool,
+        built: &mut Vec<NodeId>,
+    ) -> io::Result<()> {
+        let get = |built: &[NodeId], index: u32| {
+            built
+                .get(index as usize)
+                .copied()
+                .ok_or_else(|| invalid("node index out of range"))
+        };
+
+        for _ in 0..count {
+            let mut children = [NodeId::leaf(0); 4];
+            for child in &mut children {
+                *child = get(built, read_u32(reader)?)?;
+            }
+
+            let level = self.node(children[0]).level;
+            if children
+                .iter()
+                .any(|&child| self.node(child).level != level)
+            {
+                return Err(invalid("children of different levels"));
+            }
+
+            let [nw, ne, sw, se] = children;
+            let id = self.create_tree(nw, ne, sw, se);
+
+            if with_caches {
+                let cache = read_u32(reader)?;
+                let quick_cache = read_u32(reader)?;
+                let mut hashmap = self.hashmap.borrow_mut();
+
+                // results are one level below the node, nodes that already
+                // had one keep it
+                for (index, quick) in [(cache, false), (quick_cache, true)] {
+                    if index == NONE {
+                        continue;
+                    }
+                    let result = get(built, index)?;
+                    if hashmap[result].level + 1 != hashmap[id].level {
+                        return Err(invalid("cached result of the wrong level"));
+                    }
+
+                    let slot = if quick {
+                        &mut hashmap[id].quick_cache
+                    } else {
+                        &mut hashmap[id].cache
+                    };
+                    slot.get_or_insert(result);
+                }
+            }
+
+            built.push(id);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{cells_of, load, soup};
+
+    #[test]
+    fn round_trip() {
+        for with_caches in [false, true] {
+            let mut life = LifeUniverse::new();
+            life.set_rule("B36/S23").unwrap();
+            load(&mut life, &soup(7, 64, 64));
+            life.set_step(3);
+            for _ in 0..4 {
+                life.next_generation(true);
+            }
+
+            let path = std::env::temp_dir().join(format!("life-state-{with_caches}"));
+            let path = path.to_str().unwrap();
+            life.save_state(path, with_caches).unwrap();
+
+            let mut loaded = LifeUniverse::new();
+            loaded.load_state(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+
+            assert_eq!(loaded.get_population(), life.get_population());
+            assert_eq!(loaded.get_generation(), life.get_generation());
+            assert_eq!(loaded.get_rule(), "B36/S23");
+            assert_eq!(loaded.get_step(), 3);
+            assert!(cells_of(&loaded) == cells_of(&life));
+
+            life.next_generation(true);
+            loaded.next_generation(true);
+            assert_eq!(loaded.get_generation(), 40.0);
+            assert!(cells_of(&loaded) == cells_of(&life));
+        }
+    }
+}
@@ -0,0 +1,701 @@
+// Tests of the universe against a plain set of cells stepped one generation at
+// a time.
+
+use super::*;
+use std::collections::HashSet;
+
+const LIFE_S: usize = 1 << 2 | 1 << 3;
+const LIFE_B: usize = 1 << 3;
+
+pub fn soup(seed: u64, width: i32, height: i32) -> Vec<(i32, i32)> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    let mut cells = vec![];
+
+    for y in 0..height {
+        for x in 0..width {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state % 100 < 40 {
+                cells.push((x - width / 2, y - height / 2));
+            }
+        }
+    }
+    cells
+}
+
+pub fn load(life: &mut LifeUniverse, cells: &[(i32, i32)]) {
+    life.setup_field(
+        cells.iter().map(|c| c.0).collect(),
+        cells.iter().map(|c| c.1).collect(),
+    );
+}
+
+// the living cells of an rle file in the examples directory
+pub fn rle(name: &str) -> Vec<(i32, i32)> {
+    let path = format!("{}/../examples/{name}.rle", env!("CARGO_MANIFEST_DIR"));
+    let text = std::fs::read_to_string(path).unwrap();
+    let mut cells = vec![];
+    let (mut x, mut y, mut count) = (0, 0, 0);
+
+    let lines = text.lines().filter(|line| !line.starts_with(['#', 'x']));
+    for c in lines.flat_map(str::chars) {
+        match c {
+            '0'..='9' => count = count * 10 + c as i32 - '0' as i32,
+            '!' => break,
+            '$' => {
+                y += count.max(1);
+                x = 0;
+                count = 0;
+            }
+            'b' | '.' => {
+                x += count.max(1);
+                count = 0;
+            }
+            c if c.is_ascii_alphabetic() => {
+                cells.extend((x..x + count.max(1)).map(|x| (x, y)));
+                x += count.max(1);
+                count = 0;
+            }
+            _ => {}
+        }
+    }
+    cells
+}
+
+pub fn cells_of(life: &LifeUniverse) -> HashSet<(i64, i64)> {
+    let mut cells = HashSet::new();
+    if life.get_population() == 0 {
+        return cells;
+    }
+
+    let bounds = life.get_root_bounds();
+    for y in bounds[2] as i64..=bounds[3] as i64 {
+        for x in bounds[0] as i64..=bounds[1] as i64 {
+            if life.get_bit(x as f64, y as f64) {
+                cells.insert((x, y));
+            }
+        }
+    }
+    cells
+}
+
+// the cells of every state but 0, for multi-state rules
+pub fn states_of(life: &LifeUniverse) -> HashMap<(i64, i64), u8> {
+    let mut states = HashMap::new();
+    if life.get_population() == 0 {
+        return states;
+    }
+
+    let bounds = life.get_root_bounds();
+    for y in bounds[2] as i64..=bounds[3] as i64 {
+        for x in bounds[0] as i64..=bounds[1] as i64 {
+            let state = life.get_cell_state(x as f64, y as f64);
+            if state != 0 {
+                states.insert((x, y), state);
+            }
+        }
+    }
+    states
+}
+
+pub fn naive_step(cells: &HashSet<(i64, i64)>, s: usize, b: usize) -> HashSet<(i64, i64)> {
+    let mut counts = HashMap::<(i64, i64), usize>::new();
+    for &(x, y) in cells {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx != 0 || dy != 0 {
+                    *counts.entry((x + dx, y + dy)).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let survivors = cells
+        .iter()
+        .filter(|c| s >> counts.get(c).copied().unwrap_or(0) & 1 != 0);
+    let births = counts
+        .iter()
+        .filter(|(c, n)| !cells.contains(c) && b >> **n & 1 != 0)
+        .map(|(c, _)| c);
+    survivors.chain(births).copied().collect()
+}
+
+#[test]
+fn steps_match_naive() {
+    for seed in 1..4 {
+        let mut life = LifeUniverse::new();
+        load(&mut life, &soup(seed, 24, 24));
+        let mut naive = cells_of(&life);
+
+        for step in [0, 0, 1, 0, 2, 3] {
+            life.set_step(step);
+            life.next_generation(true);
+            for _ in 0..1 << step {
+                naive = naive_step(&naive, LIFE_S, LIFE_B);
+            }
+            assert_eq!(cells_of(&life), naive, "seed {seed} step {step}");
+        }
+    }
+}
+
+#[test]
+fn collects_without_memory_limit() {
+    let mut life = LifeUniverse::new();
+    life.set_memory_limit(0);
+    load(&mut life, &soup(1, 64, 64));
+    life.set_step(4);
+
+    let mut most = 0;
+    for _ in 0..200 {
+        life.next_generation(true);
+        most = most.max(life.hashmap.borrow().len);
+    }
+
+    let collections = life.get_stats()[4];
+    assert!(collections > 0.0, "no garbage collection");
+    // collections run after steps, which add fewer nodes than there are
+    assert!(most < 2 * INITIAL_COLLECT_NODES, "{most} nodes");
+}
+
+#[test]
+fn threads_match_one_thread() {
+    let mut one = LifeUniverse::new();
+    let mut four = LifeUniverse::new();
+    four.set_threads(4);
+
+    for life in [&mut one, &mut four] {
+        life.set_engine(Engine::Hashlife);
+        load(life, &soup(2, 256, 256));
+        life.set_step(6);
+        for _ in 0..3 {
+            life.next_generation(true);
+        }
+    }
+
+    assert!(four.get_level() >= 10);
+    assert_eq!(cells_of(&one), cells_of(&four));
+}
+
+#[test]
+fn threads_keep_memory_limit() {
+    let mut one = LifeUniverse::new();
+    load(&mut one, &soup(2, 256, 256));
+    one.set_step(6);
+    assert!(one.next_generation(true));
+
+    // too little memory for the step, or just enough when garbage is collected
+    for (limit, done) in [(400_000, false), (1_000_000, true)] {
+        let mut four = LifeUniverse::new();
+        four.set_threads(4);
+        load(&mut four, &soup(2, 256, 256));
+        four.set_memory_limit(limit);
+        four.set_step(6);
+
+        assert!(four.next_generation(true) == done, "{limit}");
+        let usage = four.get_memory_stats()[0];
+        assert!(usage <= limit as f64 * 1.01, "{usage} bytes");
+        if done {
+            assert_eq!(cells_of(&four), cells_of(&one));
+        } else {
+            assert_eq!(four.get_generation(), 0.0);
+        }
+    }
+}
+
+// cargo test --release -- --ignored --nocapture
+#[test]
+#[ignore]
+fn bench_threads() {
+    for (name, step, steps) in [("breeder1", 10, 16), ("3enginecordershipgun", 12, 8)] {
+        let cells = rle(name);
+        let mut single = None;
+
+        for threads in [1, 2, 4, 8] {
+            let mut life = LifeUniverse::new();
+            life.set_threads(threads);
+            life.set_engine(Engine::Hashlife);
+            load(&mut life, &cells);
+            life.set_step(step);
+
+            let start = std::time::Instant::now();
+            for _ in 0..steps {
+                life.next_generation(true);
+            }
+            let elapsed = start.elapsed();
+            let single = *single.get_or_insert(elapsed);
+            eprintln!(
+                "{name}, {threads} threads: {elapsed:?}, {:.2}x, population {}",
+                single.as_secs_f64() / elapsed.as_secs_f64(),
+                life.get_population()
+            );
+        }
+    }
+}
+
+#[test]
+fn quicklife_builds_root_when_needed() {
+    let mut life = LifeUniverse::new();
+    life.set_engine(Engine::QuickLife);
+    load(&mut life, &soup(4, 100, 100));
+    let mut naive = cells_of(&life);
+
+    life.next_generation(true);
+    let nodes = life.hashmap.borrow().len;
+    for _ in 0..20 {
+        life.next_generation(true);
+    }
+    // stepping the tiles makes no nodes
+    assert_eq!(life.hashmap.borrow().len, nodes);
+
+    for _ in 0..21 {
+        naive = naive_step(&naive, LIFE_S, LIFE_B);
+    }
+    assert_eq!(cells_of(&life), naive);
+    assert_eq!(life.get_population(), naive.len());
+
+    // an edit replaces the tiles, which are made again from the new root
+    life.set_bit(1000.0, 1000.0, true);
+    life.set_bit(1001.0, 1000.0, true);
+    life.set_bit(1002.0, 1000.0, true);
+    naive.extend([(1000, 1000), (1001, 1000), (1002, 1000)]);
+    life.next_generation(true);
+    assert_eq!(cells_of(&life), naive_step(&naive, LIFE_S, LIFE_B));
+
+    life.set_rules(LIFE_S, LIFE_B | 1);
+    assert!(life.get_active_engine() == Engine::Hashlife);
+}
+
+// runs a step begun with begin_step to its end
+fn finish_step(life: &mut LifeUniverse) -> StepStatus {
+    loop {
+        match life.continue_step(0.0) {
+            StepStatus::Working => continue,
+            status => return status,
+        }
+    }
+}
+
+#[test]
+fn step_statuses() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(4, 64, 64));
+    let mut naive = cells_of(&life);
+
+    life.set_step(4);
+    life.begin_step(true);
+    assert!(finish_step(&mut life) == StepStatus::Done);
+    assert!(life.continue_step(0.0) == StepStatus::Idle);
+    for _ in 0..16 {
+        naive = naive_step(&naive, LIFE_S, LIFE_B);
+    }
+    assert!(cells_of(&life) == naive);
+
+    // quicklife steps are done in one go
+    life.set_engine(Engine::QuickLife);
+    life.begin_step(true);
+    assert!(life.continue_step(0.0) == StepStatus::Done);
+    for _ in 0..16 {
+        naive = naive_step(&naive, LIFE_S, LIFE_B);
+    }
+    assert!(cells_of(&life) == naive);
+    life.set_engine(Engine::Hashlife);
+
+    life.begin_step(true);
+    life.set_bit(1000.0, 1000.0, true);
+    assert!(life.continue_step(0.0) == StepStatus::Edited);
+    assert_eq!(life.get_generation(), 32.0);
+
+    // far too little memory for the step
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(5, 200, 200));
+    life.set_memory_limit(400_000);
+    life.set_step(6);
+    life.begin_step(true);
+    assert!(finish_step(&mut life) == StepStatus::OutOfMemory);
+    assert_eq!(life.get_generation(), 0.0);
+}
+
+#[test]
+fn render_rgba() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &[(0, 0), (1, 0), (0, 1), (1, 1)]);
+
+    // the 8x8 cells from -4, -4 at one pixel per cell
+    let half = 2f64.powi(life.get_level() as i32 - 1);
+    let view = View::new(4.0 - half, 4.0 - half, 2.0 * half, 0.0, 0.0);
+    let mut buffer = vec![0; 8 * 8];
+    assert!(
+        life.render_rgba(&mut buffer[1..], 8, 8, &view, 1, 2, 0.0)
+            .is_err()
+    );
+    life.render_rgba(&mut buffer, 8, 8, &view, 1, 2, 0.0)
+        .unwrap();
+
+    for (i, &pixel) in buffer.iter().enumerate() {
+        let alive = (4..6).contains(&(i % 8)) && (4..6).contains(&(i / 8));
+        assert_eq!(pixel, if alive { 1 } else { 2 }, "{i}");
+    }
+}
+
+#[test]
+fn render_density() {
+    let mut life = LifeUniverse::new();
+    let block = [(0, 0), (1, 0), (0, 1), (1, 1)];
+    for (x, y) in block.into_iter().chain([(2, 0), (-2, -2), (-1, -2)]) {
+        life.set_bit(x as f64, y as f64, true);
+    }
+
+    // the 8x8 cells from -4, -4 at two cells per pixel
+    let half = 2f64.powi(life.get_level() as i32 - 1);
+    let view = View::new((4.0 - half) / 2.0, (4.0 - half) / 2.0, half, 0.0, 0.0);
+    let mut buffer = vec![0.0; 4 * 4];
+    assert!(life.render_density(&mut buffer[1..], 4, 4, &view).is_err());
+    life.render_density(&mut buffer, 4, 4, &view).unwrap();
+
+    for (i, &density) in buffer.iter().enumerate() {
+        let expected = match (i % 4, i / 4) {
+            (2, 2) => 1.0,
+            (3, 2) => 0.25,
+            (1, 1) => 0.5,
+            _ => 0.0,
+        };
+        assert_eq!(density, expected, "{i}");
+    }
+}
+
+#[test]
+fn cell_history_far_away() {
+    let mut life = LifeUniverse::new();
+    for x in -1..=1 {
+        life.set_bit(x as f64, 0.0, true);
+    }
+    // the root is above level 64
+    let far = 3.0 * 2f64.powi(69);
+    life.set_bit(far, far, true);
+
+    life.start_cell_history();
+    assert_eq!(life.set_step(3), 0);
+    life.next_generation(true);
+
+    let history = life.cell_history.as_ref().unwrap();
+    assert_eq!(history.last_change(far, far), 1.0);
+    // the closest cells that can be told apart from it
+    let ulp = 2f64.powi(18);
+    for (x, y) in [(far - ulp, far), (far, far + ulp), (-far, -far)] {
+        assert_eq!(history.last_change(x, y), 0.0, "{x} {y}");
+    }
+
+    // the four tips of the blinker and the far cell, each counted once
+    let mut changed = 0.0;
+    let mut stack = vec![(0, history.level)];
+    while let Some((index, level)) = stack.pop() {
+        match history.changes[index as usize] {
+            Change::At(1.0) => changed += 4f64.powi(level as i32),
+            Change::At(_) => {}
+            Change::Split(quadrants) => stack.extend(quadrants.map(|q| (q, level - 1))),
+        }
+    }
+    assert_eq!(changed, 5.0);
+}
+
+#[test]
+fn cell_history_stays_bounded() {
+    let mut life = LifeUniverse::new();
+    for x in -1..=1 {
+        life.set_bit(x as f64, 0.0, true);
+    }
+    life.start_cell_history();
+
+    let mut sizes = vec![];
+    for generation in 1..=200 {
+        life.next_generation(true);
+        let history = life.cell_history.as_ref().unwrap();
+        sizes.push(history.changes.len() - history.free.len());
+
+        // the tips and the centre alternate, the centre never changes
+        let tips = [(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)];
+        for (x, y) in tips {
+            assert_eq!(history.last_change(x, y), generation as f64);
+        }
+        assert_eq!(history.last_change(0.0, 0.0), 0.0);
+    }
+    assert_eq!(sizes[10], sizes[199]);
+}
+
+#[test]
+fn boolean_operations() {
+    let (cells_a, cells_b) = (soup(8, 120, 90), soup(9, 90, 120));
+
+    for shared in [false, true] {
+        for op in [
+            BooleanOp::Union,
+            BooleanOp::Intersect,
+            BooleanOp::Difference,
+            BooleanOp::Xor,
+        ] {
+            let mut a = LifeUniverse::new();
+            load(&mut a, &cells_a);
+            // the other universe is imported unless it shares the store
+            let mut b = if shared {
+                LifeUniverse::with_store(&a.get_store())
+            } else {
+                LifeUniverse::new()
+            };
+            load(&mut b, &cells_b);
+
+            let (set_a, set_b) = (cells_of(&a), cells_of(&b));
+            let expected: HashSet<(i64, i64)> = match op {
+                BooleanOp::Union => &set_a | &set_b,
+                BooleanOp::Intersect => &set_a & &set_b,
+                BooleanOp::Difference => &set_a - &set_b,
+                BooleanOp::Xor => &set_a ^ &set_b,
+            };
+
+            a.combine(&b, op);
+            assert!(cells_of(&a) == expected);
+            assert_eq!(a.get_population(), expected.len());
+        }
+    }
+}
+
+#[test]
+fn envelope() {
+    let mut life = LifeUniverse::new();
+    let blinker = [(-1, 0), (0, 0), (1, 0)];
+    for (x, y) in blinker {
+        life.set_bit(x as f64, y as f64, true);
+    }
+
+    let envelope = life.compute_envelope(5);
+    let expected: HashSet<(i64, i64)> = [(-1, 0), (0, 0), (1, 0), (0, -1), (0, 1)].into();
+    assert!(cells_of(&envelope) == expected);
+
+    assert_eq!(life.get_generation(), 0.0);
+    assert!(cells_of(&life) == blinker.into());
+}
+
+#[test]
+fn small_nodes_are_fixed() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(2, 64, 64));
+    let mut naive = cells_of(&life);
+
+    life.set_step(2);
+    for _ in 0..8 {
+        life.next_generation(true);
+        for _ in 0..4 {
+            naive = naive_step(&naive, LIFE_S, LIFE_B);
+        }
+    }
+    assert_eq!(cells_of(&life), naive);
+    // nodes of levels 0 to 2 are never stored
+    assert!(life.get_stats()[8..11].iter().all(|&n| n == 0.0));
+
+    // saved with their results, the fixed nodes come back too
+    let path = std::env::temp_dir().join("small_nodes_are_fixed.state");
+    let path = path.to_str().unwrap();
+    life.save_state(path, true).unwrap();
+    let mut loaded = LifeUniverse::new();
+    loaded.load_state(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    for life in [&mut life, &mut loaded] {
+        life.next_generation(true);
+    }
+    assert_eq!(cells_of(&loaded), cells_of(&life));
+}
+
+#[test]
+fn undo_redo_across_collections() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(6, 64, 64));
+    life.set_step(3);
+
+    let mut saved = vec![];
+    for _ in 0..3 {
+        life.save_undo_state();
+        saved.push((life.get_generation(), cells_of(&life)));
+        life.next_generation(true);
+    }
+    saved.push((life.get_generation(), cells_of(&life)));
+
+    assert!(life.undo() && life.undo());
+
+    // another universe on the same store, with so little memory that its
+    // steps collect everything that isn't pinned, the cached results too
+    let mut other = LifeUniverse::with_store(&life.get_store());
+    load(&mut other, &soup(7, 64, 64));
+    other.set_memory_limit(200_000);
+    other.set_step(3);
+    let collections = life.get_stats()[4];
+    for _ in 0..20 {
+        other.next_generation(true);
+    }
+    assert!(life.get_stats()[4] > collections);
+
+    for state in &saved[2..] {
+        assert!(life.redo());
+        assert!((life.get_generation(), cells_of(&life)) == *state);
+    }
+    assert!(!life.redo());
+
+    for state in saved[..3].iter().rev() {
+        assert!(life.undo());
+        assert!((life.get_generation(), cells_of(&life)) == *state);
+    }
+    assert!(!life.undo());
+}
+
+#[test]
+fn snapshots() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(8, 64, 64));
+    life.set_step(3);
+    let pins = |life: &LifeUniverse| life.hashmap.borrow().pinned.values().sum::<usize>();
+    let unpinned = pins(&life);
+
+    life.snapshot("start".to_string());
+    let start = (life.get_generation(), cells_of(&life));
+    life.next_generation(true);
+    life.snapshot("later".to_string());
+    assert!(life.list_snapshots() == ["start", "later"]);
+
+    // taken again under the same name, the old one is replaced
+    life.next_generation(true);
+    life.snapshot("later".to_string());
+    let later = (life.get_generation(), cells_of(&life));
+    assert_eq!(life.list_snapshots().len(), 2);
+
+    // so little memory that the steps collect everything that isn't pinned
+    life.set_memory_limit(200_000);
+    let collections = life.get_stats()[4];
+    for _ in 0..20 {
+        life.next_generation(true);
+    }
+    assert!(life.get_stats()[4] > collections);
+
+    assert!(life.restore("start"));
+    assert!((life.get_generation(), cells_of(&life)) == start);
+    assert!(life.restore("later"));
+    assert!((life.get_generation(), cells_of(&life)) == later);
+    assert!(!life.restore("missing"));
+
+    assert!(life.delete_snapshot("start") && life.delete_snapshot("later"));
+    assert!(!life.delete_snapshot("start"));
+    assert!(life.list_snapshots().is_empty());
+    assert_eq!(pins(&life), unpinned);
+}
+
+#[test]
+fn stats() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(9, 64, 64));
+    life.set_step(2);
+    life.reset_stats();
+    life.next_generation(true);
+
+    // the nodes are counted by their level
+    let stats = life.get_stats();
+    assert_eq!(stats[0], stats[8..].iter().sum::<f64>());
+    assert!(stats.len() > 8 + life.get_level());
+    assert!(stats[2] > 0.0 && stats[7] > 0.0);
+
+    // the same step in another universe on the store finds every result in
+    // the cache
+    let mut other = LifeUniverse::with_store(&life.get_store());
+    load(&mut other, &soup(9, 64, 64));
+    other.set_step(2);
+    other.next_generation(true);
+    let again = life.get_stats();
+    assert!(again[6] > stats[6]);
+    assert_eq!(again[7], stats[7]);
+
+    life.reset_stats();
+    assert!(other.get_stats()[4..8] == [0.0; 4]);
+}
+
+#[test]
+fn pattern_info() {
+    let mut life = LifeUniverse::new();
+    load(&mut life, &soup(10, 16, 16));
+    let mut info = PatternInfo::new();
+    info.name = "Soup".to_string();
+    info.author = "Someone".to_string();
+    info.comments = vec!["first".to_string(), "second".to_string()];
+    info.urls = vec!["https://example.com/soup".to_string()];
+    info.source_url = "https://example.com/soup".to_string();
+    life.set_pattern_info(&info);
+
+    // exporters get each link once
+    assert_eq!(
+        life.info_text(),
+        "Soup\nAuthor: Someone\nfirst\nsecond\nhttps://example.com/soup"
+    );
+    info.source_url = "https://example.com/soup.rle".to_string();
+    life.set_pattern_info(&info);
+    assert!(life.info_urls() == ["https://example.com/soup", "https://example.com/soup.rle"]);
+
+    // kept in saved states
+    let path = std::env::temp_dir().join("pattern_info.state");
+    let path = path.to_str().unwrap();
+    life.save_state(path, false).unwrap();
+    let mut loaded = LifeUniverse::new();
+    loaded.load_state(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    let PatternInfo {
+        name,
+        author,
+        comments,
+        urls,
+        source_url,
+    } = loaded.get_pattern_info();
+    assert_eq!((name, author), (info.name, info.author));
+    assert!((comments, urls, source_url) == (info.comments, info.urls, info.source_url));
+
+    // and dropped with the pattern
+    life.clear_pattern();
+    assert!(life.get_pattern_info().name.is_empty());
+    assert!(life.info_text().is_empty());
+}
+
+#[test]
+fn metapattern() {
+    let on_cells = [(0, 0), (-2, -2)];
+    let off_cells = [(1, -2)];
+    let mut on = LifeUniverse::new();
+    let mut off = LifeUniverse::new();
+    let mut pattern = LifeUniverse::new();
+    for (life, cells) in [
+        (&mut on, &on_cells[..]),
+        (&mut off, &off_cells[..]),
+        (&mut pattern, &[(0, 0), (1, 0), (-3, 2)][..]),
+    ] {
+        for &(x, y) in cells {
+            life.set_bit(x as f64, y as f64, true);
+        }
+    }
+
+    let mut meta = LifeUniverse::new();
+    meta.build_metapattern(&on, &off, &pattern, 2);
+
+    // every cell of the root of pattern becomes a 4x4 tile, made of the
+    // cells of on or off from -2, -2
+    let half = 1 << (pattern.get_level() - 1);
+    let alive = cells_of(&pattern);
+    let mut expected = HashSet::new();
+    for y in -half..half {
+        for x in -half..half {
+            let tile = if alive.contains(&(x, y)) {
+                &on_cells[..]
+            } else {
+                &off_cells[..]
+            };
+            expected.extend(
+                tile.iter()
+                    .map(|&(tx, ty)| (x * 4 + tx + 2, y * 4 + ty + 2)),
+            );
+        }
+    }
+    assert!(cells_of(&meta) == expected);
+}